@@ -0,0 +1,172 @@
+use super::orderbook::{Order, Stock};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+// decides how an incoming order's qty is shared out between the orders resting at a price
+// level. Returns (resting order id, qty) pairs in the order they should trade, each pair is
+// capped at the resting order's visible qty and whatever is left of the incoming order
+pub trait MatchingPolicy: Debug + Send + Sync {
+    fn allocate(
+        &self,
+        stock: &Stock,
+        incoming: &Order,
+        resting: &[Order],
+        qty: i32,
+    ) -> Vec<(uuid::Uuid, i32)>;
+}
+
+// policy that follows the stock's configured allocation
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StockPolicy;
+
+impl MatchingPolicy for StockPolicy {
+    fn allocate(
+        &self,
+        stock: &Stock,
+        incoming: &Order,
+        resting: &[Order],
+        qty: i32,
+    ) -> Vec<(uuid::Uuid, i32)> {
+        stock
+            .allocation
+            .allocate(incoming, resting, qty, stock.min_allocation)
+    }
+}
+
+// default policy for books that haven't been given one
+pub fn stock_policy() -> Arc<dyn MatchingPolicy> {
+    Arc::new(StockPolicy)
+}
+
+// how an incoming order's qty is shared out between the resting orders at a price level
+#[allow(non_camel_case_types)]
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub enum Allocation {
+    // strict price-time priority
+    #[default]
+    FIFO,
+    // in proportion to each order's visible qty
+    PRO_RATA,
+    // the first order in the queue is filled first, the rest is shared pro-rata
+    PRO_RATA_TOP_ORDER,
+}
+
+impl Allocation {
+    // split qty between resting orders (in queue order), returning the qty each one trades.
+    // Orders whose minimum qty constraints can't be met by their share are left out, and
+    // pro-rata shares below min_allocation are rounded down to nothing
+    pub fn allocate(
+        &self,
+        incoming: &Order,
+        resting: &[Order],
+        qty: i32,
+        min_allocation: i32,
+    ) -> Vec<(uuid::Uuid, i32)> {
+        match self {
+            Allocation::FIFO => fifo(incoming, resting, qty),
+            Allocation::PRO_RATA => pro_rata(incoming, resting, qty, min_allocation),
+            Allocation::PRO_RATA_TOP_ORDER => {
+                let top = match resting.first() {
+                    Some(top) => top,
+                    None => return Vec::new(),
+                };
+                let mut allocations = fifo(incoming, std::slice::from_ref(top), qty);
+                let top_qty: i32 = allocations.iter().map(|(_, q)| q).sum();
+                allocations.extend(pro_rata(
+                    incoming,
+                    &resting[1..],
+                    qty - top_qty,
+                    min_allocation,
+                ));
+                allocations
+            }
+        }
+    }
+}
+
+// a fixed allocation regardless of the stock's, keeping its minimum allocation
+impl MatchingPolicy for Allocation {
+    fn allocate(
+        &self,
+        stock: &Stock,
+        incoming: &Order,
+        resting: &[Order],
+        qty: i32,
+    ) -> Vec<(uuid::Uuid, i32)> {
+        Allocation::allocate(self, incoming, resting, qty, stock.min_allocation)
+    }
+}
+
+// whether a match of qty satisfies both sides' minimum qty constraints
+fn can_trade(incoming: &Order, resting: &Order, qty: i32) -> bool {
+    qty > 0 && resting.accepts_fill(qty) && incoming.meets_min_qty(qty)
+}
+
+// fill resting orders one after the other in queue order
+fn fifo(incoming: &Order, resting: &[Order], qty: i32) -> Vec<(uuid::Uuid, i32)> {
+    let mut allocations: Vec<(uuid::Uuid, i32)> = Vec::new();
+    let mut remaining = qty;
+
+    for order in resting {
+        let trade_qty = remaining.min(order.visible_qty());
+        if trade_qty <= 0 {
+            break;
+        }
+        if can_trade(incoming, order, trade_qty) {
+            allocations.push((order.order_id, trade_qty));
+            remaining -= trade_qty;
+        }
+    }
+
+    allocations
+}
+
+// share qty in proportion to visible qty, handing whatever is left after rounding
+// to the orders in queue order
+fn pro_rata(
+    incoming: &Order,
+    resting: &[Order],
+    qty: i32,
+    min_allocation: i32,
+) -> Vec<(uuid::Uuid, i32)> {
+    let total: i64 = resting.iter().map(|order| order.visible_qty() as i64).sum();
+    if qty <= 0 || total == 0 {
+        return Vec::new();
+    }
+    // everyone fills in full
+    if total <= qty as i64 {
+        return fifo(incoming, resting, qty);
+    }
+
+    let mut shares: Vec<i32> = resting
+        .iter()
+        .map(|order| {
+            let share = (qty as i64 * order.visible_qty() as i64 / total) as i32;
+            if share < min_allocation {
+                0
+            } else {
+                share
+            }
+        })
+        .collect();
+
+    let mut leftover = qty - shares.iter().sum::<i32>();
+    for (order, share) in resting.iter().zip(shares.iter_mut()) {
+        if leftover == 0 {
+            break;
+        }
+        let extra = leftover.min(order.visible_qty() - *share);
+        *share += extra;
+        leftover -= extra;
+    }
+
+    resting
+        .iter()
+        .zip(shares)
+        .filter(|(order, share)| can_trade(incoming, order, *share))
+        .map(|(order, share)| (order.order_id, share))
+        .collect()
+}
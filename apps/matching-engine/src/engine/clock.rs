@@ -0,0 +1,65 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// source of the current time (unix seconds) for time-based order handling,
+// swapped out for a manual clock in tests and replays
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> u32;
+
+    // finer grained time (unix nanoseconds) used to stamp executions
+    fn now_nanos(&self) -> u64 {
+        self.now() as u64 * 1_000_000_000
+    }
+}
+
+// wall clock time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u32 {
+        chrono::Utc::now().timestamp() as u32
+    }
+
+    fn now_nanos(&self) -> u64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_nanos() as u64,
+            Err(_) => 0,
+        }
+    }
+}
+
+// clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    time: AtomicU32,
+}
+
+impl ManualClock {
+    pub fn new(time: u32) -> Self {
+        ManualClock {
+            time: AtomicU32::new(time),
+        }
+    }
+
+    pub fn set(&self, time: u32) {
+        self.time.store(time, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u32) {
+        self.time.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u32 {
+        self.time.load(Ordering::SeqCst)
+    }
+}
+
+// default clock for books that haven't been given one
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
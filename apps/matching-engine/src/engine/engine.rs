@@ -1,22 +1,26 @@
+use super::orderbook::CancelFilter;
 use super::orderbook::Exchange;
 use super::orderbook::Execution;
 use super::orderbook::Order;
 use super::orderbook::OrderBook;
 use super::orderbook::OrderSide;
-use super::orderbook::OrderType;
 use super::orderbook::PriceLevel;
+use super::orderbook::Session;
 use super::orderbook::Stock;
 use super::orderbook::User;
+use super::price::Price;
 use crate::errors;
 use std::fmt::Debug;
 
 use std::collections::BTreeMap;
 
 use redis::AsyncCommands;
-use redis::Commands;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+// channel for rejections that aren't about an order on a known stock
+pub const REJECTS_CHANNEL: &str = "rejects";
+
 #[derive(Serialize, Deserialize)]
 // enum for change type
 pub enum ChangeType {
@@ -79,7 +83,7 @@ impl MatchingEngine {
         &mut self,
         stock_id: uuid::Uuid,
         order_side: OrderSide,
-        price: f32,
+        price: Price,
     ) -> &mut PriceLevel {
         // get orderbook given stock id
         let orderbook: &mut OrderBook =
@@ -104,41 +108,157 @@ impl MatchingEngine {
         orderbook.get_oid_map()
     }
 
-    pub fn modify_order(
+    // amend an order and publish the result, along with any trades it caused
+    pub async fn modify_order(
         &mut self,
         order_id: uuid::Uuid,
-        price: f32,
+        price: Price,
         quantity: i32,
-    ) -> Result<(), errors::OrderError> {
-        let orderbook: &mut OrderBook =
-            match self.exchange.orderbooks.get_mut(&order_id.to_string()) {
-                Some(orderbook) => orderbook,
-                None => {
-                    return Err(errors::OrderError::Other(String::from(
-                        "Orderbook not found",
-                    )))
-                }
-            };
+    ) -> Result<Execution, errors::OrderError> {
+        let order = self._known_order(order_id);
+        let exec = match self.exchange.modify_order(order_id, quantity, Some(price)) {
+            Ok(exec) => exec,
+            Err(e) => return Err(self._reject(order_id, order, e).await),
+        };
 
-        match orderbook.modify_order(order_id, quantity, Some(price)) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+        let stock = exec.order.stock.clone();
+        let channel: String = format!("stock:{}", stock.ticker);
+        let mut to_publish: Vec<Execution> = vec![exec.clone()];
+        to_publish.extend(self._drain_events(stock.stock_id));
+        self._publish(channel, &to_publish).await;
+
+        Ok(exec)
+    }
+
+    // cancel an order and submit its replacement, publishing a single REPLACE (or MATCH/CANCEL
+    // if the replacement traded) in place of a DELETE and ADD
+    pub async fn cancel_replace(
+        &mut self,
+        order_id: uuid::Uuid,
+        new_order: Order,
+    ) -> Result<Execution, errors::OrderError> {
+        let stock = new_order.stock.clone();
+        let exec = match self.exchange.cancel_replace(order_id, new_order.clone()) {
+            Ok(exec) => exec,
+            Err(e) => return Err(self._reject(order_id, Some(new_order), e).await),
+        };
+
+        let channel: String = format!("stock:{}", stock.ticker);
+        let mut to_publish: Vec<Execution> = vec![exec.clone()];
+        to_publish.extend(self._drain_events(stock.stock_id));
+        self._publish(channel, &to_publish).await;
+
+        Ok(exec)
+    }
+
+    // delete an order by id and publish the DELETE, along with any order group legs it took
+    pub async fn delete_order(
+        &mut self,
+        order_id: uuid::Uuid,
+    ) -> Result<Execution, errors::OrderError> {
+        let order = self._known_order(order_id);
+        let exec = match self.exchange.delete_order(order_id) {
+            Ok(exec) => exec,
+            Err(e) => return Err(self._reject(order_id, order, e).await),
+        };
+
+        let stock = exec.order.stock.clone();
+        let channel: String = format!("stock:{}", stock.ticker);
+        let mut to_publish: Vec<Execution> = vec![exec.clone()];
+        to_publish.extend(self._drain_events(stock.stock_id));
+        self._publish(channel, &to_publish).await;
+
+        Ok(exec)
+    }
+
+    // pull every live order matching the filter and publish each book's MASS_CANCEL on its
+    // stock's channel
+    pub async fn mass_cancel(&mut self, filter: CancelFilter) -> Vec<Execution> {
+        let executions = self.exchange.mass_cancel(filter);
+        self._publish_mass_cancels(executions).await
+    }
+
+    // register a client session with the exchange
+    pub fn open_session(&mut self, session: Session) {
+        self.exchange.open_session(session);
+    }
+
+    // for the API layer to call when a client connection drops, publishes whatever the
+    // session's cancel on disconnect pulled
+    pub async fn disconnect(&mut self, session_id: uuid::Uuid) -> Vec<Execution> {
+        let executions = self.exchange.disconnect(session_id);
+        self._publish_mass_cancels(executions).await
+    }
+
+    // publish one MASS_CANCEL per book, followed by any order group cancels it caused
+    async fn _publish_mass_cancels(&mut self, mass_cancels: Vec<Execution>) -> Vec<Execution> {
+        let mut executions: Vec<Execution> = Vec::new();
+
+        for exec in mass_cancels {
+            let stock = exec.order.stock.clone();
+            let mut to_publish: Vec<Execution> = vec![exec];
+            to_publish.extend(self._drain_events(stock.stock_id));
+            self._publish(format!("stock:{}", stock.ticker), &to_publish)
+                .await;
+            executions.extend(to_publish);
         }
+
+        executions
     }
 
-    pub fn delete_order(&mut self, order_id: uuid::Uuid) -> Result<(), errors::OrderError> {
-        let orderbook = match self.exchange.orderbooks.get_mut(&order_id.to_string()) {
-            Some(orderbook) => orderbook,
-            None => {
-                return Err(errors::OrderError::Other(String::from(
-                    "Orderbook not found",
-                )))
-            }
+    // put a stock into call-auction mode
+    pub fn start_auction(&mut self, stock_id: uuid::Uuid) -> Result<(), errors::OrderError> {
+        self.exchange.start_auction(stock_id)
+    }
+
+    // uncross a stock's auction and publish the resulting executions
+    pub async fn uncross(
+        &mut self,
+        stock_id: uuid::Uuid,
+    ) -> Result<Vec<Execution>, errors::OrderError> {
+        let ticker: String = match self.exchange.orderbooks.get(&stock_id.to_string()) {
+            Some(orderbook) => orderbook.stock_info.ticker.clone(),
+            None => return Err(errors::OrderError::InvalidStockID),
         };
 
-        match orderbook.delete_order(order_id) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+        let mut executions = self.exchange.uncross(stock_id)?;
+        executions.extend(self._drain_events(stock_id));
+
+        let channel: String = format!("stock:{}", ticker);
+        self._publish(channel, &executions).await;
+
+        Ok(executions)
+    }
+
+    // expire GTD/DAY orders in every book and publish the expiries on each stock's channel
+    pub async fn expire_orders(&mut self) -> Vec<Execution> {
+        let executions = self.exchange.expire_orders();
+
+        let mut expired: BTreeMap<String, Vec<Execution>> = BTreeMap::new();
+        for exec in executions.iter() {
+            expired
+                .entry(format!("stock:{}", exec.order.stock.ticker))
+                .or_default()
+                .push(exec.clone());
+        }
+        for (channel, execs) in expired {
+            self._publish(channel, &execs).await;
+        }
+
+        executions
+    }
+
+    // publish executions to a redis pub sub channel
+    async fn _publish(&self, channel: String, executions: &[Execution]) {
+        let mut pubsub_conn = match self.client.get_async_connection().await {
+            Ok(conn) => conn,
+            Err(e) => panic!("Error connecting to redis: {:?}", e),
+        };
+
+        for exec in executions {
+            let data = json!(exec);
+            let data = serde_json::to_string(&data).unwrap();
+            let _: () = pubsub_conn.publish(channel.clone(), data).await.unwrap();
         }
     }
 
@@ -147,75 +267,159 @@ impl MatchingEngine {
         // add stock to self.exchange
         match self.exchange.add_stock(stock.clone(), issuer) {
             Ok(_) => Ok(()),
-            Err(e) => return Err(e),
+            Err(e) => Err(e),
         }
     }
 
     pub async fn execute_order(&mut self, order: Order) -> Result<Execution, errors::OrderError> {
-        // get orderbook given stock id
-        let orderbook: &mut OrderBook = match self
+        if !self
             .exchange
             .orderbooks
-            .get_mut(&order.clone().stock.stock_id.to_string())
+            .contains_key(&order.stock.stock_id.to_string())
         {
-            Some(orderbook) => orderbook,
-            None => {
-                return Err(errors::OrderError::Other(String::from(
-                    "Orderbook not found",
-                )))
-            }
+            let error = errors::OrderError::InvalidStockID;
+            return Err(self._reject(order.order_id, Some(order), error).await);
+        }
+
+        // validate, queue and execute order
+        let exec = match self.exchange.execute_order(order.clone()) {
+            Ok(exec) => exec,
+            Err(e) => return Err(self._reject(order.order_id, Some(order), e).await),
         };
 
-        //queue and execute order
-        orderbook.queue_order(order.clone());
+        // publish execution to redis pub sub for a stock ticker channel,
+        // followed by any events it caused (e.g. stop triggers)
+        let channel: String = format!("stock:{}", order.stock.ticker);
+        let mut to_publish: Vec<Execution> = vec![exec.clone()];
+        to_publish.extend(self._drain_events(order.stock.stock_id));
+        self._publish(channel, &to_publish).await;
 
-        // execute order and publish execution to redis pub sub for a stock ticker channel
-        let channel: String = format!("stock:{}", order.clone().stock.ticker);
-        let mut pubsub_conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
-            Err(e) => panic!("Error connecting to redis: {:?}", e),
+        Ok(exec)
+    }
+
+    // submit a one-cancels-other pair and publish the executions
+    pub async fn submit_oco(
+        &mut self,
+        first: Order,
+        second: Order,
+    ) -> Result<Vec<Execution>, errors::OrderError> {
+        let stock = first.stock.clone();
+        let mut executions = match self.exchange.submit_oco(first.clone(), second.clone()) {
+            Ok(executions) => executions,
+            Err(e) => return Err(self._reject_all(vec![first, second], e).await),
         };
+        executions.extend(self._drain_events(stock.stock_id));
+        self._publish(format!("stock:{}", stock.ticker), &executions)
+            .await;
 
-        match orderbook.execute_order() {
-            Ok(exec) => {
-                // publish execution to redis pub sub for a stock ticker channel
-                let data = json!(exec);
-                let data = serde_json::to_string(&data).unwrap();
-                // publish
-                let _: () = pubsub_conn.publish(channel, data).await.unwrap();
-                Ok(exec)
-            },
-            Err(e) => Err(e),
+        Ok(executions)
+    }
+
+    // submit a bracket (entry plus take-profit and stop-loss legs) and publish the executions
+    pub async fn submit_bracket(
+        &mut self,
+        entry: Order,
+        take_profit: Order,
+        stop_loss: Order,
+    ) -> Result<Vec<Execution>, errors::OrderError> {
+        let stock = entry.stock.clone();
+        let orders = vec![entry.clone(), take_profit.clone(), stop_loss.clone()];
+        let mut executions = match self.exchange.submit_bracket(entry, take_profit, stop_loss) {
+            Ok(executions) => executions,
+            Err(e) => return Err(self._reject_all(orders, e).await),
+        };
+        executions.extend(self._drain_events(stock.stock_id));
+        self._publish(format!("stock:{}", stock.ticker), &executions)
+            .await;
+
+        Ok(executions)
+    }
+
+    // latest known version of an order, live or finished
+    fn _known_order(&self, order_id: uuid::Uuid) -> Option<Order> {
+        let live = self
+            .exchange
+            .get_order_stock(order_id)
+            .and_then(|stock_id| self.exchange.orderbooks.get(&stock_id.to_string()))
+            .and_then(|orderbook| orderbook.oid_map.get(&order_id));
+        match live {
+            Some(order) => Some(order.clone()),
+            None => self
+                .exchange
+                .get_order_status(order_id)
+                .map(|state| state.order.clone()),
         }
     }
 
-    // execute all orders with self.execute order and return a vec of excecutions
-    pub async fn execute_all_orders(&mut self) -> Result<Vec<Execution>, errors::OrderError> {
-        let mut executions: Vec<Execution> = Vec::new();
-        for orderbook in self.exchange.orderbooks.values_mut() {
-            match orderbook.execute_all_orders() {
-                Ok(execs) => {
-                    for exec in execs {
-                        executions.push(exec);
-                    }
-                }
-                Err(e) => return Err(e),
+    // publish a REJECT report for the order a request was about on its stock's channel,
+    // handing back the error for the caller. Requests about order ids the engine has never
+    // seen have no stock, so they're reported (unsequenced) on the rejects channel
+    async fn _reject(
+        &mut self,
+        order_id: uuid::Uuid,
+        order: Option<Order>,
+        error: errors::OrderError,
+    ) -> errors::OrderError {
+        match order {
+            Some(order) => self._reject_all(vec![order], error).await,
+            None => {
+                let exec = Execution::reject_unknown(order_id, &error, self.exchange.clock.now());
+                self._publish(String::from(REJECTS_CHANNEL), &[exec]).await;
+                error
             }
         }
+    }
 
-        // execute order and publish execution to redis pub sub for a stock ticker channel
-        let channel: String = format!("stock:{}", executions.clone()[0].order.stock.ticker);
-        let mut pubsub_conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
-            Err(e) => panic!("Error connecting to redis: {:?}", e),
-        };
+    // publish a REJECT report for each order of a rejected request
+    async fn _reject_all(
+        &mut self,
+        orders: Vec<Order>,
+        error: errors::OrderError,
+    ) -> errors::OrderError {
+        let mut rejects: BTreeMap<String, Vec<Execution>> = BTreeMap::new();
+        for order in orders {
+            let mut exec = Execution::reject(order.clone(), &error, self.exchange.clock.now());
+            if let Some(orderbook) = self
+                .exchange
+                .orderbooks
+                .get_mut(&order.stock.stock_id.to_string())
+            {
+                orderbook.stamp(&mut exec);
+            }
+            rejects
+                .entry(format!("stock:{}", order.stock.ticker))
+                .or_default()
+                .push(exec);
+        }
+        for (channel, execs) in rejects {
+            self._publish(channel, &execs).await;
+        }
 
-        for exec in executions.clone() {
-            // publish execution to redis pub sub for a stock ticker channel
-            let data = json!(exec);
-            let data = serde_json::to_string(&data).unwrap();
-            // publish
-            let _: () = pubsub_conn.publish(channel.clone(), data).await.unwrap();
+        error
+    }
+
+    // take the events a stock's orderbook has built up
+    fn _drain_events(&mut self, stock_id: uuid::Uuid) -> Vec<Execution> {
+        match self.exchange.orderbooks.get_mut(&stock_id.to_string()) {
+            Some(orderbook) => orderbook.drain_events(),
+            None => Vec::new(),
+        }
+    }
+
+    // execute every queued order through the exchange and publish the executions on each
+    // stock's channel
+    pub async fn execute_all_orders(&mut self) -> Result<Vec<Execution>, errors::OrderError> {
+        let executions = self.exchange.execute_all_orders()?;
+
+        let mut executed: BTreeMap<String, Vec<Execution>> = BTreeMap::new();
+        for exec in executions.iter() {
+            executed
+                .entry(format!("stock:{}", exec.order.stock.ticker))
+                .or_default()
+                .push(exec.clone());
+        }
+        for (channel, execs) in executed {
+            self._publish(channel, &execs).await;
         }
 
         Ok(executions)
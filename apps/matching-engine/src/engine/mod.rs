@@ -1,2 +1,6 @@
+pub mod allocation;
+pub mod clock;
+#[allow(clippy::module_inception)]
 pub mod engine;
 pub mod orderbook;
+pub mod price;
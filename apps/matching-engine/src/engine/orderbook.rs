@@ -1,12 +1,17 @@
 use core::fmt;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::hash::Hash;
+use std::ops::Bound;
+use std::sync::Arc;
 // TODO: Is this ok?
+use super::allocation::{self, Allocation, MatchingPolicy};
+use super::clock::{self, Clock};
+use super::price::Price;
 use crate::errors::OrderError;
 use crate::errors::StockError;
-use crate::helpers::helpers;
 use prettytable::{row, Table};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -19,18 +24,106 @@ pub enum OrderSide {
     ASK,
 }
 
+// STOP and STOP_LIMIT orders wait in the trigger book until last_market_price
+// reaches their stop price, then enter the book as MARKET and LIMIT orders.
+// Trailing stops do the same, but their stop price follows last_market_price
+#[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OrderType {
     MARKET,
     LIMIT,
+    STOP,
+    STOP_LIMIT,
+    TRAILING_STOP,
+    TRAILING_STOP_LIMIT,
 }
 
+// how far past the opposite side's best price at arrival a market order may trade
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MarketProtection {
+    // number of ticks
+    TICKS(u32),
+    // percentage of the best price, in basis points (1/100th of a percent)
+    BASIS_POINTS(u32),
+}
+
+// distance a trailing stop keeps from the last market price
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrailingOffset {
+    // fixed price distance
+    AMOUNT(Price),
+    // percentage of the last price, in basis points (1/100th of a percent)
+    BASIS_POINTS(u32),
+}
+
+// how long an order stays working: good-till-cancel rests the remainder,
+// immediate-or-cancel cancels it, fill-or-kill trades in full or not at all,
+// good-till-date rests until expire_time and day orders until the end of the (UTC) day
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum TimeInForce {
+    #[default]
+    GTC,
+    IOC,
+    FOK,
+    GTD,
+    DAY,
+}
+
+// price a pegged order tracks: the best price on its own side (primary), the best price on
+// the opposite side (market), or the midpoint
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PegReference {
+    PRIMARY,
+    MARKET,
+    MIDPOINT,
+}
+
+// what to do with a post-only order that would take liquidity on arrival:
+// reject it, or move its price one tick behind the opposite side's best price
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PostOnly {
+    REJECT,
+    REPRICE,
+}
+
+// what happens when an incoming order meets a resting order from the same creator
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SelfTradePrevention {
+    // cancel the rest of the incoming order
+    CANCEL_NEWEST,
+    // cancel the resting order and keep matching
+    CANCEL_OLDEST,
+    // cancel both orders
+    CANCEL_BOTH,
+    // reduce both orders by the smaller qty, cancelling whichever reaches zero
+    DECREMENT_AND_CANCEL,
+}
+
+// continuous matching, or a call auction where orders accumulate until the book is uncrossed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum TradingPhase {
+    #[default]
+    CONTINUOUS,
+    AUCTION,
+}
+
+#[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ExecutionType {
     ADD,
     MODIFY,
     DELETE,
     MATCH,
+    CANCEL,
+    TRIGGER,
+    UNCROSS,
+    EXPIRE,
+    REPLACE,
+    REJECT,
+    MASS_CANCEL,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -39,7 +132,61 @@ pub struct Execution {
     pub executor_id: uuid::Uuid,
     pub time_executed: u32,
     pub order: Order,
-    pub matched_order: Option<Order>,
+    pub fills: Vec<Fill>,
+    // qty that would have traded against the creator's own resting orders
+    #[serde(default)]
+    pub prevented_qty: i32,
+    // indicative auction state, published with each order accepted during an auction
+    #[serde(default)]
+    pub auction: Option<AuctionState>,
+    // order group the execution belongs to, for OCO/bracket legs
+    #[serde(default)]
+    pub group_id: Option<uuid::Uuid>,
+    // the order as it was before being amended
+    #[serde(default)]
+    pub previous_order: Option<Order>,
+    // position in the book's stream of executions, consecutive so consumers can spot gaps
+    #[serde(default)]
+    pub seq_num: u64,
+    // engine time (unix nanoseconds) the execution was sequenced at
+    #[serde(default)]
+    pub engine_time: u64,
+    // why the order or request was rejected, for REJECT executions
+    #[serde(default)]
+    pub reject: Option<Reject>,
+    // every order taken off the book by a MASS_CANCEL
+    #[serde(default)]
+    pub cancelled_orders: Vec<Order>,
+}
+
+// reason code (see OrderError::code) and text sent back with a rejection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Reject {
+    pub code: u32,
+    pub reason: String,
+}
+
+// equilibrium price of a call auction, i.e. the price that maximizes executed volume,
+// and the qty left over on the heavier side at that price
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuctionState {
+    pub price: Option<Price>,
+    pub volume: i32,
+    pub imbalance: i32,
+    pub imbalance_side: Option<OrderSide>,
+}
+
+// a single trade between the incoming (taker) order and one resting (maker) order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Fill {
+    pub trade_id: uuid::Uuid,
+    pub price: Price,
+    pub qty: i32,
+    pub maker_order_id: uuid::Uuid,
+    pub taker_order_id: uuid::Uuid,
+    pub maker_id: uuid::Uuid,
+    pub taker_id: uuid::Uuid,
+    pub aggressor_side: OrderSide,
 }
 
 impl fmt::Display for Execution {
@@ -49,10 +196,17 @@ impl fmt::Display for Execution {
             ExecutionType::MODIFY => "MODIFY",
             ExecutionType::DELETE => "DELETE",
             ExecutionType::MATCH => "MATCH",
+            ExecutionType::CANCEL => "CANCEL",
+            ExecutionType::TRIGGER => "TRIGGER",
+            ExecutionType::UNCROSS => "UNCROSS",
+            ExecutionType::EXPIRE => "EXPIRE",
+            ExecutionType::REPLACE => "REPLACE",
+            ExecutionType::REJECT => "REJECT",
+            ExecutionType::MASS_CANCEL => "MASS_CANCEL",
         };
         write!(
             f,
-            "Execution: {} {} {} {} {} {} {} {} {}",
+            "Execution: {} {} {} {} {} {} {} {} {} ({} fills)",
             exec_type,
             self.executor_id,
             self.time_executed,
@@ -61,7 +215,23 @@ impl fmt::Display for Execution {
             self.order.stock.stock_id,
             self.order.order_side,
             self.order.order_type,
-            self.order.qty
+            self.order.qty,
+            self.fills.len()
+        )
+    }
+}
+
+impl fmt::Display for Fill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Fill: {} {} {} @ {} maker {} taker {}",
+            self.trade_id,
+            self.aggressor_side,
+            self.qty,
+            self.price,
+            self.maker_order_id,
+            self.taker_order_id
         )
     }
 }
@@ -74,6 +244,46 @@ pub struct Stock {
     pub total_issued: Option<i32>,
     pub outstanding_shares: Option<i32>,
     pub time_created: Option<u32>,
+    // number of decimal places prices for this stock are quoted in
+    #[serde(default = "default_price_scale")]
+    pub price_scale: u32,
+    // minimum price increment, order prices must be a multiple of it
+    #[serde(default = "default_tick_size")]
+    pub tick_size: Price,
+    // minimum trade unit, order quantities must be a multiple of it
+    #[serde(default = "default_lot_size")]
+    pub lot_size: i32,
+    pub min_order_qty: Option<i32>,
+    pub max_order_qty: Option<i32>,
+    // bounds on price * qty of a single order
+    pub min_notional: Option<Price>,
+    pub max_notional: Option<Price>,
+    // how qty is shared between the orders resting at a price level
+    #[serde(default)]
+    pub allocation: Allocation,
+    // pro-rata shares smaller than this are not allocated
+    #[serde(default)]
+    pub min_allocation: i32,
+}
+
+// most decimal places a stock can be quoted in, so that one whole unit still fits in a Price
+pub const MAX_PRICE_SCALE: u32 = 18;
+
+fn default_price_scale() -> u32 {
+    2
+}
+
+fn default_tick_size() -> Price {
+    Price::new(1, default_price_scale())
+}
+
+fn default_lot_size() -> i32 {
+    1
+}
+
+// keep finished orders queryable for a day by default
+fn default_status_retention() -> u32 {
+    86400
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -85,12 +295,51 @@ pub struct Order {
     pub order_type: OrderType,
     pub qty: i32,
     pub time_created: u32,
-    pub price: Option<f32>,
+    pub price: Option<Price>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    // unix time at which a GTD order expires, set by the book for DAY orders
+    #[serde(default)]
+    pub expire_time: Option<u32>,
+    // price at which a STOP/STOP_LIMIT order is released into the book
+    #[serde(default)]
+    pub stop_price: Option<Price>,
+    // how far a trailing stop's stop price trails the last market price
+    #[serde(default)]
+    pub trailing_offset: Option<TrailingOffset>,
+    // iceberg orders only show display_qty at a time, the rest is held in hidden_qty
+    #[serde(default)]
+    pub display_qty: Option<i32>,
+    #[serde(default)]
+    pub hidden_qty: i32,
+    // maker-only orders never take liquidity
+    #[serde(default)]
+    pub post_only: Option<PostOnly>,
+    // pegged orders have their price set from the top of book, plus peg_offset if given
+    #[serde(default)]
+    pub peg: Option<PegReference>,
+    #[serde(default)]
+    pub peg_offset: Option<Price>,
+    // self-trade prevention applied when this order takes liquidity, None allows self-trades
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    // smallest qty the order will trade in a single match (or its whole remainder if less)
+    #[serde(default)]
+    pub min_qty: Option<i32>,
+    // only trade the whole order at once
+    #[serde(default)]
+    pub all_or_none: bool,
+    // slippage limit for market orders (and stops that become market orders)
+    #[serde(default)]
+    pub market_protection: Option<MarketProtection>,
+    // order this one replaced through a cancel/replace
+    #[serde(default)]
+    pub replaced_order_id: Option<uuid::Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceLevel {
-    pub price: f32,
+    pub price: Price,
     pub qty: i32,
     pub orders: VecDeque<uuid::Uuid>,
 }
@@ -99,11 +348,42 @@ pub struct PriceLevel {
 pub struct OrderBook {
     pub stock_id: uuid::Uuid,
     pub stock_info: Stock,
-    pub bid_price_levels: BTreeMap<String, PriceLevel>,
-    pub ask_price_levels: BTreeMap<String, PriceLevel>,
+    pub bid_price_levels: BTreeMap<Price, PriceLevel>,
+    pub ask_price_levels: BTreeMap<Price, PriceLevel>,
     pub oid_map: BTreeMap<uuid::Uuid, Order>,
     pub order_queue: VecDeque<Order>,
-    pub last_market_price: Option<f32>,
+    pub last_market_price: Option<Price>,
+    pub trigger_book: TriggerBook,
+    // events produced as a side effect of matching (e.g. stop triggers), drained by the caller
+    pub events: VecDeque<Execution>,
+    #[serde(default)]
+    pub trading_phase: TradingPhase,
+    // time source for expiring GTD/DAY orders
+    #[serde(skip, default = "clock::system_clock")]
+    pub clock: Arc<dyn Clock>,
+    // how fills are shared out between orders at a price level
+    #[serde(skip, default = "allocation::stock_policy")]
+    pub policy: Arc<dyn MatchingPolicy>,
+    // sequence number of the last execution the book put out
+    #[serde(default)]
+    pub seq_num: u64,
+}
+
+// where an order taken out of the book came from, to put it back if what follows fails
+#[allow(clippy::upper_case_acronyms)]
+enum TakenFrom {
+    LEVEL(PriceLevel),
+    TRIGGERS(VecDeque<uuid::Uuid>),
+}
+
+// stop orders waiting for their trigger, kept outside the visible book
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TriggerBook {
+    // buy stops trigger when the last price rises to their stop price
+    pub bid_triggers: BTreeMap<Price, VecDeque<uuid::Uuid>>,
+    // sell stops trigger when the last price falls to their stop price
+    pub ask_triggers: BTreeMap<Price, VecDeque<uuid::Uuid>>,
+    pub orders: BTreeMap<uuid::Uuid, Order>,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 // struct for user
@@ -123,12 +403,102 @@ pub struct UserStocks {
     quantity: i32,
 }
 
+// one-cancels-other legs reduce each other as they fill, bracket legs are an OCO pair
+// (take-profit and stop-loss) that is only armed once its entry order has filled
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OrderGroupType {
+    OCO,
+    BRACKET,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OrderGroup {
+    pub group_id: uuid::Uuid,
+    pub group_type: OrderGroupType,
+    pub stock_id: uuid::Uuid,
+    // bracket entry order, None for OCO groups and once the entry has filled
+    pub entry: Option<Order>,
+    pub entry_filled: i32,
+    // linked legs with their remaining qty, working in the book once armed
+    pub legs: Vec<Order>,
+    pub armed: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Exchange {
     pub users: BTreeMap<uuid::Uuid, User>,
     pub stocks: BTreeMap<uuid::Uuid, Stock>,
     pub user_stocks: BTreeMap<uuid::Uuid, HashMap<uuid::Uuid, UserStocks>>,
     pub orderbooks: BTreeMap<String, OrderBook>,
+    #[serde(default)]
+    pub order_groups: BTreeMap<uuid::Uuid, OrderGroup>,
+    // order id -> id of the group it belongs to
+    #[serde(default)]
+    pub group_orders: BTreeMap<uuid::Uuid, uuid::Uuid>,
+    // latest state of every order the exchange has seen
+    #[serde(default)]
+    pub order_registry: BTreeMap<uuid::Uuid, OrderState>,
+    // creator id -> ids of their orders in the registry
+    #[serde(default)]
+    pub creator_orders: BTreeMap<uuid::Uuid, BTreeSet<uuid::Uuid>>,
+    // order id -> stock id, for every live order
+    #[serde(default)]
+    pub order_stocks: BTreeMap<uuid::Uuid, uuid::Uuid>,
+    // open client sessions by session id
+    #[serde(default)]
+    pub sessions: BTreeMap<uuid::Uuid, Session>,
+    // orders that reached a final status, oldest first, with the time they did
+    #[serde(default)]
+    pub finished_orders: VecDeque<(u32, uuid::Uuid)>,
+    // seconds finished orders stay in the registry
+    #[serde(default = "default_status_retention")]
+    pub status_retention: u32,
+    // time source handed to every orderbook
+    #[serde(skip, default = "clock::system_clock")]
+    pub clock: Arc<dyn Clock>,
+}
+
+// where an order is in its lifecycle
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OrderStatus {
+    NEW,
+    PARTIALLY_FILLED,
+    FILLED,
+    CANCELLED,
+    EXPIRED,
+    REJECTED,
+}
+
+// latest known state of an order, kept around after it leaves the book
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OrderState {
+    // the order as last seen, its qty is whatever was left to trade
+    pub order: Order,
+    pub status: OrderStatus,
+    pub filled_qty: i32,
+    // sum of price * qty over every fill
+    pub filled_notional: Price,
+    pub time_updated: u32,
+}
+
+// which live orders a mass cancel applies to, fields left as None match any order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct CancelFilter {
+    pub creator_id: Option<uuid::Uuid>,
+    pub stock_id: Option<uuid::Uuid>,
+    pub order_side: Option<OrderSide>,
+}
+
+// a client connection, the orders it covers are pulled when it drops if cancel_on_disconnect
+// is set. stock_id/order_side left as None cover all of the creator's orders
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    pub session_id: uuid::Uuid,
+    pub creator_id: uuid::Uuid,
+    pub stock_id: Option<uuid::Uuid>,
+    pub order_side: Option<OrderSide>,
+    pub cancel_on_disconnect: bool,
 }
 
 impl fmt::Display for OrderType {
@@ -136,6 +506,22 @@ impl fmt::Display for OrderType {
         match self {
             OrderType::MARKET => write!(f, "MARKET"),
             OrderType::LIMIT => write!(f, "LIMIT"),
+            OrderType::STOP => write!(f, "STOP"),
+            OrderType::STOP_LIMIT => write!(f, "STOP_LIMIT"),
+            OrderType::TRAILING_STOP => write!(f, "TRAILING_STOP"),
+            OrderType::TRAILING_STOP_LIMIT => write!(f, "TRAILING_STOP_LIMIT"),
+        }
+    }
+}
+
+impl fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeInForce::GTC => write!(f, "GTC"),
+            TimeInForce::IOC => write!(f, "IOC"),
+            TimeInForce::FOK => write!(f, "FOK"),
+            TimeInForce::GTD => write!(f, "GTD"),
+            TimeInForce::DAY => write!(f, "DAY"),
         }
     }
 }
@@ -154,6 +540,10 @@ impl fmt::Display for Order {
         let order_type = match self.order_type {
             OrderType::MARKET => "MARKET",
             OrderType::LIMIT => "LIMIT",
+            OrderType::STOP => "STOP",
+            OrderType::STOP_LIMIT => "STOP_LIMIT",
+            OrderType::TRAILING_STOP => "TRAILING_STOP",
+            OrderType::TRAILING_STOP_LIMIT => "TRAILING_STOP_LIMIT",
         };
         let order_side = match self.order_side {
             OrderSide::BID => "BID",
@@ -198,13 +588,100 @@ impl Stock {
             }
         }
         if let Some(time_created) = self.time_created {
-            if time_created <= 0 {
+            if time_created == 0 {
                 return Err(StockError::InvalidTimeCreated);
             }
         }
+        if self.price_scale > MAX_PRICE_SCALE {
+            return Err(StockError::InvalidPriceScale);
+        }
+        if !self.tick_size.is_positive() || self.tick_size.scale > self.price_scale {
+            return Err(StockError::InvalidTickSize);
+        }
+        if self.lot_size <= 0 {
+            return Err(StockError::InvalidLotSize);
+        }
+        if let (Some(min_qty), Some(max_qty)) = (self.min_order_qty, self.max_order_qty) {
+            if min_qty > max_qty {
+                return Err(StockError::InvalidOrderLimits);
+            }
+        }
+        if let (Some(min_notional), Some(max_notional)) = (self.min_notional, self.max_notional) {
+            if min_notional > max_notional {
+                return Err(StockError::InvalidOrderLimits);
+            }
+        }
+        if self.min_allocation < 0 {
+            return Err(StockError::InvalidAllocation);
+        }
         Ok(()) // If all checks pass, return Ok(())
     }
 
+    // check an order against this stock's trading constraints
+    pub fn validate_order(&self, order: &Order) -> Result<(), OrderError> {
+        if let Some(price) = order.price {
+            if !price.is_multiple_of(self.tick_size) {
+                return Err(OrderError::PriceNotOnTick);
+            }
+        }
+        if let Some(stop_price) = order.stop_price {
+            if !stop_price.is_multiple_of(self.tick_size) {
+                return Err(OrderError::PriceNotOnTick);
+            }
+        }
+        if let Some(TrailingOffset::AMOUNT(amount)) = order.trailing_offset {
+            if !amount.is_multiple_of(self.tick_size) {
+                return Err(OrderError::PriceNotOnTick);
+            }
+        }
+        if let Some(peg_offset) = order.peg_offset {
+            if !peg_offset.is_multiple_of(self.tick_size) {
+                return Err(OrderError::PriceNotOnTick);
+            }
+        }
+        if order.qty % self.lot_size != 0 {
+            return Err(OrderError::QuantityNotOnLot);
+        }
+        if let Some(display_qty) = order.display_qty {
+            if display_qty % self.lot_size != 0 {
+                return Err(OrderError::QuantityNotOnLot);
+            }
+        }
+        if let Some(min_qty) = order.min_qty {
+            if min_qty % self.lot_size != 0 {
+                return Err(OrderError::QuantityNotOnLot);
+            }
+        }
+        if let Some(min_qty) = self.min_order_qty {
+            if order.qty < min_qty {
+                return Err(OrderError::QuantityBelowMinimum);
+            }
+        }
+        if let Some(max_qty) = self.max_order_qty {
+            if order.qty > max_qty {
+                return Err(OrderError::QuantityAboveMaximum);
+            }
+        }
+        // notional can only be checked when the order has a price, one too large to
+        // represent is above any maximum
+        if let Some(price) = order.price {
+            let notional = price
+                .notional(order.qty)
+                .ok_or(OrderError::NotionalAboveMaximum)?;
+            if let Some(min_notional) = self.min_notional {
+                if notional < min_notional {
+                    return Err(OrderError::NotionalBelowMinimum);
+                }
+            }
+            if let Some(max_notional) = self.max_notional {
+                if notional > max_notional {
+                    return Err(OrderError::NotionalAboveMaximum);
+                }
+            }
+        }
+        Ok(())
+    }
+
     // return new Stock
     pub fn new(
         stock_id: uuid::Uuid,
@@ -218,9 +695,18 @@ impl Stock {
             stock_id,
             name,
             ticker,
-            total_issued: total_issued,
-            outstanding_shares: outstanding_shares,
-            time_created: time_created,
+            total_issued,
+            outstanding_shares,
+            time_created,
+            price_scale: default_price_scale(),
+            tick_size: default_tick_size(),
+            lot_size: default_lot_size(),
+            min_order_qty: None,
+            max_order_qty: None,
+            min_notional: None,
+            max_notional: None,
+            allocation: Allocation::FIFO,
+            min_allocation: 0,
         };
 
         match stock.validate() {
@@ -230,6 +716,11 @@ impl Stock {
 
         stock
     }
+
+    // convert a decimal price into this stock's fixed-point representation
+    pub fn price(&self, value: f64) -> Price {
+        Price::from_f64(value, self.price_scale)
+    }
 }
 
 impl Order {
@@ -243,27 +734,118 @@ impl Order {
         if self.stock.stock_id == uuid::Uuid::nil() {
             return Err(OrderError::InvalidStockID);
         }
-        match self.price {
-            Some(price) => {
-                if price <= 0.0 {
-                    return Err(OrderError::InvalidPrice);
+        if let Some(price) = self.price {
+            // prices must be positive and quoted at the stock's scale
+            if !price.is_positive() || price.scale != self.stock.price_scale {
+                return Err(OrderError::InvalidPrice);
+            }
+        }
+        // limit orders need a price, unless they're pegged and take it from the book
+        if self.order_type == OrderType::LIMIT && self.price.is_none() && self.peg.is_none() {
+            return Err(OrderError::InvalidPrice);
+        }
+        // stop orders need a stop price, stop-limit orders a limit price as well
+        match self.order_type {
+            OrderType::STOP | OrderType::STOP_LIMIT => match self.stop_price {
+                Some(stop_price)
+                    if stop_price.is_positive() && stop_price.scale == self.stock.price_scale => {}
+                _ => return Err(OrderError::InvalidStopPrice),
+            },
+            // trailing stops work out their stop price from the last price if not given one
+            OrderType::TRAILING_STOP | OrderType::TRAILING_STOP_LIMIT => {
+                match self.stop_price {
+                    Some(stop_price)
+                        if !stop_price.is_positive()
+                            || stop_price.scale != self.stock.price_scale =>
+                    {
+                        return Err(OrderError::InvalidStopPrice)
+                    }
+                    _ => {}
                 }
+                match self.trailing_offset {
+                    Some(TrailingOffset::AMOUNT(amount))
+                        if amount.is_positive() && amount.scale == self.stock.price_scale => {}
+                    Some(TrailingOffset::BASIS_POINTS(bps)) if bps > 0 && bps < 10000 => {}
+                    _ => return Err(OrderError::InvalidTrailingOffset),
+                }
+            }
+            _ => {}
+        }
+        if matches!(
+            self.order_type,
+            OrderType::STOP_LIMIT | OrderType::TRAILING_STOP_LIMIT
+        ) && self.price.is_none()
+        {
+            return Err(OrderError::InvalidPrice);
+        }
+        // post-only orders need a limit price to rest at
+        if self.post_only.is_some() && self.order_type != OrderType::LIMIT {
+            return Err(OrderError::InvalidPrice);
+        }
+        // only limit orders can be pegged, and offsets are quoted like prices
+        if self.peg.is_some() && self.order_type != OrderType::LIMIT {
+            return Err(OrderError::InvalidPeg);
+        }
+        if let Some(peg_offset) = self.peg_offset {
+            if self.peg.is_none() || peg_offset.scale != self.stock.price_scale {
+                return Err(OrderError::InvalidPeg);
+            }
+        }
+        // only priced orders can be icebergs
+        if let Some(display_qty) = self.display_qty {
+            if display_qty <= 0
+                || !matches!(
+                    self.order_type,
+                    OrderType::LIMIT | OrderType::STOP_LIMIT | OrderType::TRAILING_STOP_LIMIT
+                )
+            {
+                return Err(OrderError::InvalidDisplayQuantity);
+            }
+        }
+        if let Some(min_qty) = self.min_qty {
+            if min_qty <= 0 || min_qty > self.qty {
+                return Err(OrderError::InvalidMinQuantity);
+            }
+        }
+        // protection only applies to orders that trade at market
+        if let Some(protection) = self.market_protection {
+            let valid = match protection {
+                MarketProtection::TICKS(ticks) => ticks > 0,
+                MarketProtection::BASIS_POINTS(bps) => bps > 0 && bps < 10000,
+            };
+            if !valid
+                || !matches!(
+                    self.order_type,
+                    OrderType::MARKET | OrderType::STOP | OrderType::TRAILING_STOP
+                )
+            {
+                return Err(OrderError::InvalidMarketProtection);
             }
-            None => {}
+        }
+        // an all-or-none order can't show only part of itself
+        if self.all_or_none && self.display_qty.is_some() {
+            return Err(OrderError::InvalidMinQuantity);
         }
         match self.qty {
             qty if qty <= 0 => return Err(OrderError::InvalidQuantity),
             qty if qty > 1000000 => return Err(OrderError::InvalidQuantity),
             _ => {}
         }
-        match self.time_created {
-            time if time <= 0 => return Err(OrderError::InvalidTimeCreated),
-            _ => {}
+        if self.time_created == 0 {
+            return Err(OrderError::InvalidTimeCreated);
+        }
+        // good-till-date orders need an expiry after their creation time
+        if self.time_in_force == TimeInForce::GTD {
+            match self.expire_time {
+                Some(expire_time) if expire_time > self.time_created => {}
+                _ => return Err(OrderError::InvalidExpireTime),
+            }
         }
 
         Ok(()) // If all checks pass, return Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         order_id: uuid::Uuid,
         creator_id: uuid::Uuid,
@@ -272,7 +854,100 @@ impl Order {
         order_type: OrderType,
         qty: i32,
         time_created: u32,
-        price: Option<f32>,
+        price: Option<Price>,
+    ) -> Result<Self, OrderError> {
+        let order = Order::_new(
+            order_id,
+            creator_id,
+            stock,
+            order_side,
+            order_type,
+            qty,
+            time_created,
+            price,
+        );
+
+        // invalid orders are never handed out
+        order.validate()?;
+
+        Ok(order)
+    }
+
+    // create a stop, stop-limit or trailing stop order, price is the limit price once triggered
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_stop(
+        order_id: uuid::Uuid,
+        creator_id: uuid::Uuid,
+        stock: Stock,
+        order_side: OrderSide,
+        order_type: OrderType,
+        qty: i32,
+        time_created: u32,
+        price: Option<Price>,
+        stop_price: Option<Price>,
+        trailing_offset: Option<TrailingOffset>,
+    ) -> Result<Self, OrderError> {
+        let mut order = Order::_new(
+            order_id,
+            creator_id,
+            stock,
+            order_side,
+            order_type,
+            qty,
+            time_created,
+            price,
+        );
+        if !order.is_stop() {
+            return Err(OrderError::InvalidStopPrice);
+        }
+        order.stop_price = stop_price;
+        order.trailing_offset = trailing_offset;
+
+        order.validate()?;
+
+        Ok(order)
+    }
+
+    // create a limit order pegged to a reference price, it's priced when it reaches the book
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_pegged(
+        order_id: uuid::Uuid,
+        creator_id: uuid::Uuid,
+        stock: Stock,
+        order_side: OrderSide,
+        qty: i32,
+        time_created: u32,
+        peg: PegReference,
+        peg_offset: Option<Price>,
+    ) -> Result<Self, OrderError> {
+        let mut order = Order::_new(
+            order_id,
+            creator_id,
+            stock,
+            order_side,
+            OrderType::LIMIT,
+            qty,
+            time_created,
+            None,
+        );
+        order.peg = Some(peg);
+        order.peg_offset = peg_offset;
+
+        order.validate()?;
+
+        Ok(order)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn _new(
+        order_id: uuid::Uuid,
+        creator_id: uuid::Uuid,
+        stock: Stock,
+        order_side: OrderSide,
+        order_type: OrderType,
+        qty: i32,
+        time_created: u32,
+        price: Option<Price>,
     ) -> Self {
         let order: Order = Order {
             order_id,
@@ -283,15 +958,147 @@ impl Order {
             qty,
             time_created,
             price,
+            time_in_force: TimeInForce::GTC,
+            expire_time: None,
+            stop_price: None,
+            trailing_offset: None,
+            display_qty: None,
+            hidden_qty: 0,
+            post_only: None,
+            peg: None,
+            peg_offset: None,
+            self_trade_prevention: None,
+            min_qty: None,
+            all_or_none: false,
+            market_protection: None,
+            replaced_order_id: None,
         };
 
-        match order.validate() {
-            Ok(_) => {}
-            Err(e) => println!("Error validating order: {:?}", e),
+        order
+    }
+
+    // whether this order is willing to trade at the given price
+    pub fn crosses(&self, price: Price) -> bool {
+        match (self.order_type, self.price) {
+            (OrderType::LIMIT, Some(limit)) => match self.order_side {
+                OrderSide::BID => price <= limit,
+                OrderSide::ASK => price >= limit,
+            },
+            _ => true,
         }
+    }
 
-        // TODO: Is this how it is done?
-        order
+    // whether a stop order's trigger has been reached by the given last price
+    pub fn is_triggered(&self, last_price: Price) -> bool {
+        match self.stop_price {
+            Some(stop_price) => match self.order_side {
+                OrderSide::BID => last_price >= stop_price,
+                OrderSide::ASK => last_price <= stop_price,
+            },
+            None => false,
+        }
+    }
+
+    // time at which the order stops working, None if it never expires
+    pub fn expires_at(&self) -> Option<u32> {
+        match self.time_in_force {
+            // day orders are given theirs by the book that accepts them
+            TimeInForce::GTD | TimeInForce::DAY => self.expire_time,
+            _ => None,
+        }
+    }
+
+    // start of the (UTC) day after the given time, the last day saturates instead of wrapping
+    pub fn end_of_day(now: u32) -> u32 {
+        const SECS_PER_DAY: u32 = 86400;
+        (now - now % SECS_PER_DAY).saturating_add(SECS_PER_DAY)
+    }
+
+    pub fn is_expired(&self, now: u32) -> bool {
+        match self.expires_at() {
+            Some(expires_at) => now >= expires_at,
+            None => false,
+        }
+    }
+
+    // whether a single match of qty satisfies the order's minimum qty
+    pub fn meets_min_qty(&self, qty: i32) -> bool {
+        match self.min_qty {
+            Some(min_qty) => qty >= min_qty.min(self.qty),
+            None => true,
+        }
+    }
+
+    // whether a resting order's minimum qty and all-or-none constraints let it trade qty
+    // in a single match
+    pub fn accepts_fill(&self, qty: i32) -> bool {
+        match self.all_or_none {
+            true => qty == self.qty,
+            false => self.meets_min_qty(qty),
+        }
+    }
+
+    // qty shown in the book, for iceberg orders this is only the current slice
+    pub fn visible_qty(&self) -> i32 {
+        self.qty - self.hidden_qty
+    }
+
+    // show a new slice of an iceberg order, moving the rest into the hidden reserve
+    pub fn replenish(&mut self) {
+        self.hidden_qty = match self.display_qty {
+            Some(display_qty) => self.qty - display_qty.min(self.qty),
+            None => 0,
+        };
+    }
+
+    // whether the order waits in the trigger book
+    pub fn is_stop(&self) -> bool {
+        matches!(
+            self.order_type,
+            OrderType::STOP
+                | OrderType::STOP_LIMIT
+                | OrderType::TRAILING_STOP
+                | OrderType::TRAILING_STOP_LIMIT
+        )
+    }
+
+    // the order a stop order turns into once triggered
+    pub fn activate(&mut self) {
+        match self.order_type {
+            OrderType::STOP | OrderType::TRAILING_STOP => self.order_type = OrderType::MARKET,
+            OrderType::STOP_LIMIT | OrderType::TRAILING_STOP_LIMIT => {
+                self.order_type = OrderType::LIMIT
+            }
+            _ => {}
+        }
+    }
+
+    // stop price a trailing stop would have with the given last price, rounded away from the
+    // last price onto the tick grid
+    pub fn trailing_stop_price(&self, last_price: Price, tick_size: Price) -> Option<Price> {
+        let offset = match self.trailing_offset? {
+            TrailingOffset::AMOUNT(amount) => amount,
+            TrailingOffset::BASIS_POINTS(bps) => Price::new(
+                last_price.ticks.checked_mul(bps as i64)? / 10000,
+                last_price.scale,
+            ),
+        };
+        match self.order_side {
+            OrderSide::BID => last_price.checked_add(offset)?.ceil_to(tick_size),
+            OrderSide::ASK => last_price.checked_sub(offset)?.floor_to(tick_size),
+        }
+    }
+
+    // move a trailing stop's stop price, dragging a stop-limit's limit price along with it
+    pub fn trail_to(&mut self, stop_price: Price) {
+        if let (Some(old_stop), Some(price)) = (self.stop_price, self.price) {
+            // a limit price that can't move that far keeps its old value
+            let moved = stop_price
+                .checked_sub(old_stop)
+                .and_then(|delta| price.checked_add(delta));
+            self.price = moved.or(self.price);
+        }
+        self.stop_price = Some(stop_price);
     }
 }
 
@@ -302,42 +1109,245 @@ impl Execution {
         executor_id: uuid::Uuid,
         time_executed: u32,
         order: Order,
-        matched_order: Option<Order>,
+        fills: Vec<Fill>,
     ) -> Self {
         let execution: Execution = Execution {
             exec_type,
             executor_id,
             time_executed,
             order,
-            matched_order,
+            fills,
+            prevented_qty: 0,
+            auction: None,
+            group_id: None,
+            previous_order: None,
+            seq_num: 0,
+            engine_time: 0,
+            reject: None,
+            cancelled_orders: Vec::new(),
         };
 
         execution
     }
-}
 
-impl PriceLevel {
-    pub fn new(price: f32, qty: i32) -> Self {
-        let price_level: PriceLevel = PriceLevel {
-            price,
-            qty,
-            orders: VecDeque::new(),
-        };
+    // report orders pulled from a book in one go, order is the first of them. None if there
+    // are no orders to report
+    pub fn mass_cancel(
+        executor_id: uuid::Uuid,
+        time_executed: u32,
+        orders: Vec<Order>,
+    ) -> Option<Self> {
+        let first = orders.first()?.clone();
+        let mut execution = Execution::new(
+            ExecutionType::MASS_CANCEL,
+            executor_id,
+            time_executed,
+            first,
+            Vec::new(),
+        );
+        execution.cancelled_orders = orders;
 
-        price_level
+        Some(execution)
     }
 
-    // add order id to price level
-    pub fn add_order(&mut self, order: Order) {
-        self.orders.push_back(order.order_id);
-        self.qty += order.qty;
+    // report a rejected order (or a rejected request on an existing order)
+    pub fn reject(order: Order, error: &OrderError, time_executed: u32) -> Self {
+        let mut execution = Execution::new(
+            ExecutionType::REJECT,
+            order.creator_id,
+            time_executed,
+            order,
+            Vec::new(),
+        );
+        execution.reject = Some(Reject {
+            code: error.code(),
+            reason: error.reason(),
+        });
+
+        execution
     }
 
-    // remove order id from price level
-    pub fn remove_order(&mut self, order: Order) {
+    // report a rejected request about an order id the exchange has never seen. there's no
+    // order or stock to report on, so the order only carries the requested id
+    pub fn reject_unknown(order_id: uuid::Uuid, error: &OrderError, time_executed: u32) -> Self {
+        let stock = Stock {
+            stock_id: uuid::Uuid::nil(),
+            name: String::new(),
+            ticker: String::new(),
+            total_issued: None,
+            outstanding_shares: None,
+            time_created: None,
+            price_scale: default_price_scale(),
+            tick_size: default_tick_size(),
+            lot_size: default_lot_size(),
+            min_order_qty: None,
+            max_order_qty: None,
+            min_notional: None,
+            max_notional: None,
+            allocation: Allocation::FIFO,
+            min_allocation: 0,
+        };
+        let order = Order::_new(
+            order_id,
+            uuid::Uuid::nil(),
+            stock,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            0,
+            0,
+            None,
+        );
+
+        Execution::reject(order, error, time_executed)
+    }
+
+    // total quantity traded across all fills
+    pub fn filled_qty(&self) -> i32 {
+        self.fills.iter().map(|fill| fill.qty).sum()
+    }
+}
+
+impl Fill {
+    // create new fill between a taker and a maker order
+    pub fn new(taker: &Order, maker: &Order, price: Price, qty: i32) -> Self {
+        let fill: Fill = Fill {
+            trade_id: uuid::Uuid::new_v4(),
+            price,
+            qty,
+            maker_order_id: maker.order_id,
+            taker_order_id: taker.order_id,
+            maker_id: maker.creator_id,
+            taker_id: taker.creator_id,
+            aggressor_side: taker.order_side,
+        };
+
+        fill
+    }
+}
+
+impl PriceLevel {
+    pub fn new(price: Price, qty: i32) -> Self {
+        let price_level: PriceLevel = PriceLevel {
+            price,
+            qty,
+            orders: VecDeque::new(),
+        };
+
+        price_level
+    }
+
+    // add order id to price level, only the visible qty counts towards the level
+    pub fn add_order(&mut self, order: Order) {
+        self.orders.push_back(order.order_id);
+        self.qty += order.visible_qty();
+    }
+
+    // remove order id from price level
+    pub fn remove_order(&mut self, order: Order) {
         // get order from oid map
         self.orders.retain(|&x| x != order.order_id);
-        self.qty -= order.qty;
+        self.qty -= order.visible_qty();
+    }
+}
+
+impl TriggerBook {
+    // create new trigger book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    pub fn contains(&self, order_id: &uuid::Uuid) -> bool {
+        self.orders.contains_key(order_id)
+    }
+
+    // add a stop order, keyed on its stop price
+    pub fn add_order(&mut self, order: Order) {
+        let triggers = match order.order_side {
+            OrderSide::BID => &mut self.bid_triggers,
+            OrderSide::ASK => &mut self.ask_triggers,
+        };
+        triggers
+            .entry(order.stop_price.unwrap())
+            .or_default()
+            .push_back(order.order_id);
+        self.orders.insert(order.order_id, order);
+    }
+
+    // remove a stop order, returning it if it was present
+    pub fn remove_order(&mut self, order_id: uuid::Uuid) -> Option<Order> {
+        let order = self.orders.remove(&order_id)?;
+        let triggers = match order.order_side {
+            OrderSide::BID => &mut self.bid_triggers,
+            OrderSide::ASK => &mut self.ask_triggers,
+        };
+        let stop_price = order.stop_price.unwrap();
+        if let Some(queue) = triggers.get_mut(&stop_price) {
+            queue.retain(|&x| x != order_id);
+            if queue.is_empty() {
+                triggers.remove(&stop_price);
+            }
+        }
+        Some(order)
+    }
+
+    // ratchet trailing stops towards the last price, they never move away from it
+    pub fn trail(&mut self, last_price: Price, tick_size: Price) {
+        let trailed: Vec<(uuid::Uuid, Price)> = self
+            .orders
+            .values()
+            .filter_map(|order| {
+                let new_stop = order.trailing_stop_price(last_price, tick_size)?;
+                let improved = match order.order_side {
+                    OrderSide::BID => new_stop < order.stop_price?,
+                    OrderSide::ASK => new_stop > order.stop_price?,
+                };
+                improved.then_some((order.order_id, new_stop))
+            })
+            .collect();
+
+        for (order_id, new_stop) in trailed {
+            if let Some(mut order) = self.remove_order(order_id) {
+                order.trail_to(new_stop);
+                self.add_order(order);
+            }
+        }
+    }
+
+    // remove and return every stop order triggered by the last price,
+    // nearest stop prices first and in time priority within a price
+    pub fn take_triggered(&mut self, last_price: Price) -> Vec<Order> {
+        let bid_prices: Vec<Price> = self
+            .bid_triggers
+            .range(..=last_price)
+            .map(|(p, _)| *p)
+            .collect();
+        let ask_prices: Vec<Price> = self
+            .ask_triggers
+            .range(last_price..)
+            .rev()
+            .map(|(p, _)| *p)
+            .collect();
+
+        let mut triggered: Vec<Order> = Vec::new();
+        for price in bid_prices {
+            for order_id in self.bid_triggers.remove(&price).unwrap_or_default() {
+                triggered.extend(self.orders.remove(&order_id));
+            }
+        }
+        for price in ask_prices {
+            for order_id in self.ask_triggers.remove(&price).unwrap_or_default() {
+                triggered.extend(self.orders.remove(&order_id));
+            }
+        }
+        triggered
     }
 }
 
@@ -352,6 +1362,12 @@ impl OrderBook {
             oid_map: BTreeMap::new(),
             order_queue: VecDeque::new(),
             last_market_price: None,
+            trigger_book: TriggerBook::new(),
+            events: VecDeque::new(),
+            trading_phase: TradingPhase::CONTINUOUS,
+            clock: clock::system_clock(),
+            policy: allocation::stock_policy(),
+            seq_num: 0,
         };
 
         orderbook
@@ -361,15 +1377,37 @@ impl OrderBook {
     pub fn get_price_level(
         &mut self,
         order_side: OrderSide,
-        price: f32,
+        price: Price,
     ) -> Option<&mut PriceLevel> {
         match order_side {
-            OrderSide::BID => self
-                .bid_price_levels
-                .get_mut(&helpers::f32_to_string(price, 2)),
-            OrderSide::ASK => self
-                .ask_price_levels
-                .get_mut(&helpers::f32_to_string(price, 2)),
+            OrderSide::BID => self.bid_price_levels.get_mut(&price),
+            OrderSide::ASK => self.ask_price_levels.get_mut(&price),
+        }
+    }
+
+    // highest bid price in the book
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bid_price_levels.keys().next_back().copied()
+    }
+
+    // lowest ask price in the book
+    pub fn best_ask(&self) -> Option<Price> {
+        self.ask_price_levels.keys().next().copied()
+    }
+
+    // difference between best ask and best bid, None if either side is empty
+    pub fn spread(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some(ask - bid),
+            _ => None,
+        }
+    }
+
+    // midpoint between best bid and best ask, None if either side is empty
+    pub fn mid(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => bid.checked_add(ask)?.half(),
+            _ => None,
         }
     }
 
@@ -382,30 +1420,37 @@ impl OrderBook {
         Ok(stock)
     }
 
-    pub fn queue_order(&mut self, order: Order) {
+    // queue a valid order for execution, it must also meet the stock's trading constraints
+    pub fn queue_order(&mut self, order: Order) -> Result<(), OrderError> {
+        order.validate()?;
+        self.stock_info.validate_order(&order)?;
         self.order_queue.push_back(order);
+        Ok(())
     }
 
+    // add an order to the book, enforcing the stock's trading constraints
     pub fn add_order(&mut self, mut order: Order) -> Result<(), OrderError> {
-        let price_key: String;
-
-        match order.order_type {
-            OrderType::MARKET => {
-                // get last market price, and set order.price to the last market price
-                match self.last_market_price {
-                    Some(last_market_price) => {
-                        order.price = Some(last_market_price);
-                    }
-                    None => {
-                        // don't add order to orderbook and return error
-                        return Err(OrderError::InvalidPrice);
-                    }
-                }
-            }
-            _ => {}
+        self.stock_info.validate_order(&order)?;
+        self._set_day_expiry(&mut order);
+        self._add_order(order)
+    }
+
+    // day orders last until the end of the day the book first accepts them, not the day
+    // they claim to have been created
+    fn _set_day_expiry(&self, order: &mut Order) {
+        if order.time_in_force == TimeInForce::DAY && order.expire_time.is_none() {
+            order.expire_time = Some(Order::end_of_day(self.clock.now()));
         }
+    }
 
-        price_key = helpers::f32_to_string(order.price.unwrap(), 2);
+    // add an order to the book without checking constraints (used for unfilled remainders)
+    fn _add_order(&mut self, mut order: Order) -> Result<(), OrderError> {
+        // market orders never rest in the book
+        let price_key: Price = match (order.order_type, order.price) {
+            (OrderType::LIMIT, Some(price)) => price,
+            _ => return Err(OrderError::InvalidPrice),
+        };
+        order.replenish();
 
         // add order to oid map and price level
         self.oid_map.insert(order.order_id, order.clone());
@@ -433,21 +1478,24 @@ impl OrderBook {
         Ok(())
     }
 
-    fn _remove_price_level(&mut self, order_side: OrderSide, price: f32) {
+    fn _remove_price_level(&mut self, order_side: OrderSide, price: Price) {
         match order_side {
             OrderSide::BID => {
-                self.bid_price_levels
-                    .remove(&helpers::f32_to_string(price, 2));
+                self.bid_price_levels.remove(&price);
             }
             OrderSide::ASK => {
-                self.ask_price_levels
-                    .remove(&helpers::f32_to_string(price, 2));
+                self.ask_price_levels.remove(&price);
             }
         }
     }
 
     // delete an order (affects pricelevel and orderbook)
     fn _delete_order(&mut self, order_id: uuid::Uuid) -> Result<(), OrderError> {
+        // stop orders that haven't triggered yet only live in the trigger book
+        if self.trigger_book.remove_order(order_id).is_some() {
+            return Ok(());
+        }
+
         let order: Order = match self.oid_map.get(&order_id) {
             Some(order) => order.clone(),
             None => return Err(OrderError::InvalidOrderID),
@@ -462,8 +1510,8 @@ impl OrderBook {
 
         price_level.remove_order(order.clone());
 
-        // delete price level if qty is les than 0
-        if price_level.qty <= 0 {
+        // delete price level once it has no orders left
+        if price_level.orders.is_empty() {
             self._remove_price_level(order.clone().order_side, order.clone().price.unwrap());
         }
 
@@ -475,7 +1523,9 @@ impl OrderBook {
 
     // wrapper for _delete_order that actually emits a deletion event
     pub fn delete_order(&mut self, order_id: uuid::Uuid) -> Result<(), OrderError> {
-        self._delete_order(order_id)
+        self._delete_order(order_id)?;
+        self._reprice_pegged_orders();
+        Ok(())
     }
 
     // modifying an order (affects pricelevel and orderbook)
@@ -483,7 +1533,7 @@ impl OrderBook {
         &mut self,
         order_id: uuid::Uuid,
         new_qty: i32,
-        new_price: Option<f32>,
+        new_price: Option<Price>,
     ) -> Result<(), OrderError> {
         // remove order if the new qty is 0 or less
         if new_qty <= 0 {
@@ -505,6 +1555,7 @@ impl OrderBook {
 
         order.qty = new_qty;
         order.price = new_price;
+        order.replenish();
 
         price_level.add_order(order.clone());
 
@@ -514,283 +1565,1195 @@ impl OrderBook {
         Ok(())
     }
 
-    // wrapper for _modify_order that actually emits a modification event
+    // amend a resting order's qty and/or price. Reducing the qty keeps the order's place in the
+    // queue, a price change or qty increase sends it to the back, matching it first if the new
+    // price crosses the book. The execution carries the order before and after the amend
     pub fn modify_order(
         &mut self,
         order_id: uuid::Uuid,
         new_qty: i32,
-        new_price: Option<f32>,
-    ) -> Result<(), OrderError> {
-        self._modify_order(order_id, new_qty, new_price)
+        new_price: Option<Price>,
+    ) -> Result<Execution, OrderError> {
+        let mut execution = self._amend_order(order_id, new_qty, new_price)?;
+        self.stamp(&mut execution);
+        // the top of book may have moved
+        self._reprice_pegged_orders();
+        Ok(execution)
     }
 
-    // match order against orderbook given order id in orderbook
-    pub fn match_order(&mut self, mut order: Order) -> Result<Execution, OrderError> {
-        let price_level_to_search = match order.order_side {
-            OrderSide::BID => self.ask_price_levels.clone(),
-            OrderSide::ASK => self.bid_price_levels.clone(),
+    fn _amend_order(
+        &mut self,
+        order_id: uuid::Uuid,
+        new_qty: i32,
+        new_price: Option<Price>,
+    ) -> Result<Execution, OrderError> {
+        // untriggered stops can be amended as well as resting orders
+        let resting = self.oid_map.contains_key(&order_id);
+        let previous = match self
+            .oid_map
+            .get(&order_id)
+            .or_else(|| self.trigger_book.orders.get(&order_id))
+        {
+            Some(order) => order.clone(),
+            None => return Err(OrderError::InvalidOrderID),
         };
+        let now = self.clock.now();
 
-        let it = price_level_to_search.iter();
-        let order_to_match: Order = order.clone(); 
+        // amending down to nothing deletes the order
+        if new_qty <= 0 {
+            self._delete_order(order_id)?;
+            let mut execution = Execution::new(
+                ExecutionType::DELETE,
+                previous.creator_id,
+                now,
+                previous.clone(),
+                Vec::new(),
+            );
+            execution.previous_order = Some(previous);
+            return Ok(execution);
+        }
 
-        match order.order_side {
-            OrderSide::BID => {
-                for (_, p_level) in it {
-                    if order.qty == 0
-                        || (order.order_type == OrderType::LIMIT
-                            && p_level.price > order.price.unwrap())
-                    {
-                        break;
-                    }
+        // only stops that turn into market orders can go without a price
+        if new_price.is_none()
+            && !matches!(
+                previous.order_type,
+                OrderType::STOP | OrderType::TRAILING_STOP
+            )
+        {
+            return Err(OrderError::InvalidPrice);
+        }
+        // pegged orders take their price from the book
+        if previous.peg.is_some() && new_price != previous.price {
+            return Err(OrderError::InvalidPeg);
+        }
+        let mut order = previous.clone();
+        order.qty = new_qty;
+        order.price = new_price;
+        order.validate()?;
+        self.stock_info.validate_order(&order)?;
 
-                    let order_it = p_level.orders.iter();
+        // anything but a qty reduction on a resting order loses its priority
+        if !resting || new_price != previous.price || new_qty > previous.qty {
+            return self._rematch_amended_order(order_id, order);
+        }
 
-                    for order_to_match_uuid in order_it {
-                        if order.qty == 0 {
-                            break;
-                        }
+        // reductions come out of an iceberg's hidden reserve first
+        order.hidden_qty = (previous.hidden_qty - (previous.qty - new_qty)).max(0);
+        let order_side = order.order_side;
+        let price_level = match new_price.and_then(|price| self.get_price_level(order_side, price))
+        {
+            Some(price_level) => price_level,
+            None => return Err(OrderError::InvalidPrice),
+        };
+        price_level.qty -= previous.visible_qty() - order.visible_qty();
+        self.oid_map.insert(order_id, order.clone());
 
-                        let mut order_to_match = match self.oid_map.get(order_to_match_uuid) {
-                            Some(order) => order.clone(),
-                            None => return Err(OrderError::InvalidOrderID),
-                        };
-
-                        if order_to_match.qty > order.qty {
-                            // order in orderbook has more qty than order to match
-                            // subtract order qty from orderbook order qty
-                            // subtract order qty from order qty
-                            // add trade to tradebook
-                            // delete order from orderbook
-                            // modify order in orderbook
-                            // return
-                            let trade_qty: i32 = order.qty;
-                            order.qty -= trade_qty;
-                            order_to_match.qty -= trade_qty;
-
-                            match self._modify_order(
-                                order_to_match.order_id,
-                                order_to_match.qty,
-                                order_to_match.price,
-                            ) {
-                                Ok(_) => {}
-                                Err(e) => return Err(e),
-                            }
-
-                            // set last market price to the ask price
-                            self.last_market_price = Some(order_to_match.price.unwrap());
-                        } else {
-                            // order in orderbook has less or equal qty than order to match
-                            // subtract order qty from orderbook order qty
-                            // subtract order qty from order qty
-                            // add trade to tradebook
-                            // delete order from orderbook
-                            // modify order in orderbook
-                            // continue
-                            let trade_qty = order_to_match.qty;
-                            order.qty -= trade_qty;
-                            order_to_match.qty -= trade_qty;
-
-                            match self._delete_order(order_to_match.order_id) {
-
-                                Ok(_) => {}
-                                Err(e) => return Err(e),
-                            }
-
-                            // set last market price to the ask price
-                            self.last_market_price = Some(order_to_match.price.unwrap());
-                        }
-                    }
-                }
+        let mut execution = Execution::new(
+            ExecutionType::MODIFY,
+            order.creator_id,
+            now,
+            order,
+            Vec::new(),
+        );
+        execution.previous_order = Some(previous);
+        Ok(execution)
+    }
+
+    // an amended order loses its priority and is matched like a new order, stops go back to
+    // waiting in the trigger book
+    fn _rematch_amended_order(
+        &mut self,
+        order_id: uuid::Uuid,
+        order: Order,
+    ) -> Result<Execution, OrderError> {
+        let (previous, taken_from) = self._take_order(order_id)?;
+        let mut execution = match self._match_order(order) {
+            Ok(execution) => execution,
+            Err(e) => {
+                self._restore_order(previous, taken_from);
+                return Err(e);
             }
-            OrderSide::ASK => {
-                for (_, p_level) in it.rev() {
-                    if order.qty == 0
-                        || (order.order_type == OrderType::LIMIT
-                            && p_level.price < order.price.unwrap())
-                    {
-                        break;
-                    }
+        };
+        if execution.exec_type == ExecutionType::ADD {
+            execution.exec_type = ExecutionType::MODIFY;
+        }
+        execution.previous_order = Some(previous);
+        Ok(execution)
+    }
 
-                    let order_it = p_level.orders.iter();
+    // cancel an order and submit its replacement in one go, the replacement is linked to the
+    // original and gets its own order id. If either part fails the book is left untouched
+    pub fn cancel_replace(
+        &mut self,
+        order_id: uuid::Uuid,
+        new_order: Order,
+    ) -> Result<Execution, OrderError> {
+        let mut execution = self._cancel_replace(order_id, new_order)?;
+        self.stamp(&mut execution);
+        // the top of book may have moved
+        self._reprice_pegged_orders();
+        Ok(execution)
+    }
 
-                    for order_to_match_uuid in order_it {
-                        if order.qty == 0 {
-                            break;
-                        }
+    fn _cancel_replace(
+        &mut self,
+        order_id: uuid::Uuid,
+        mut new_order: Order,
+    ) -> Result<Execution, OrderError> {
+        let original = match self
+            .oid_map
+            .get(&order_id)
+            .or_else(|| self.trigger_book.orders.get(&order_id))
+        {
+            Some(order) => order.clone(),
+            None => return Err(OrderError::InvalidOrderID),
+        };
 
-                        let mut order_to_match = match self.oid_map.get(order_to_match_uuid) {
-                            Some(order) => order.clone(),
-                            None => return Err(OrderError::InvalidOrderID),
-                        };
-
-                        if order_to_match.qty > order.qty {
-                            // order in orderbook has more qty than order to match
-                            // subtract order qty from orderbook order qty
-                            // subtract order qty from order qty
-                            // add trade to tradebook
-                            // delete order from orderbook
-                            // modify order in orderbook
-                            // return
-                            let trade_qty = order.qty;
-                            order.qty -= trade_qty;
-                            order_to_match.qty -= trade_qty;
-                            // instead of doing this, we create a new transaction, and eventually send it to the redis instance
-                            // let trade = Trade::new(order.order_id, order.stock_id, order.price, trade_qty);
-                            // self.tradebook.push_back(trade);
-                            match self._modify_order(
-                                order_to_match.order_id,
-                                order_to_match.qty,
-                                order_to_match.price,
-                            ) {
-                                Ok(_) => {}
-                                Err(e) => return Err(e),
-                            }
-
-                            // set last market price to the ask price
-                            self.last_market_price = Some(order_to_match.price.unwrap());
-                        } else {
-                            // order in orderbook has less or equal qty than order to match
-                            // subtract order qty from orderbook order qty
-                            // subtract order qty from order qty
-                            // add trade to tradebook
-                            // delete order from orderbook
-                            // modify order in orderbook
-                            // continue
-                            let trade_qty = order_to_match.qty;
-                            order.qty -= trade_qty;
-                            order_to_match.qty -= trade_qty;
-                            // instead of doing this, we create a new transaction, and eventually send it to the redis instance
-                            // let trade = Trade::new(order.order_id, order.stock_id, order.price, trade_qty);
-                            // self.tradebook.push_back(trade);
-                            match self._delete_order(order_to_match.order_id) {
-                                Ok(_) => {}
-                                Err(e) => return Err(e),
-                            }
-
-                            // set last market price to the ask price
-                            self.last_market_price = Some(order_to_match.price.unwrap());
-                        }
-                    }
-                }
+        // the replacement is a new order for the same creator, stock and side
+        if new_order.order_id == order_id
+            || self.oid_map.contains_key(&new_order.order_id)
+            || self.trigger_book.contains(&new_order.order_id)
+        {
+            return Err(OrderError::InvalidOrderID);
+        }
+        if new_order.stock.stock_id != self.stock_id {
+            return Err(OrderError::InvalidStockID);
+        }
+        if new_order.creator_id != original.creator_id {
+            return Err(OrderError::InvalidCreatorID);
+        }
+        if new_order.order_side != original.order_side {
+            return Err(OrderError::InvalidOrderSide);
+        }
+        new_order.replaced_order_id = Some(order_id);
+        new_order.validate()?;
+        self.stock_info.validate_order(&new_order)?;
+
+        let (original, taken_from) = self._take_order(order_id)?;
+        let mut execution = match self._match_order(new_order) {
+            Ok(execution) => execution,
+            Err(e) => {
+                self._restore_order(original, taken_from);
+                return Err(e);
             }
+        };
+        if execution.exec_type == ExecutionType::ADD {
+            execution.exec_type = ExecutionType::REPLACE;
         }
+        execution.previous_order = Some(original);
+        Ok(execution)
+    }
 
-        // add order to orderbook if it still has qty
-        if order.qty > 0 {
-            match self.add_order(order.clone()) {
-                Ok(_) => Ok(Execution::new(
-                    ExecutionType::ADD,
-                    order.creator_id,
-                    order.time_created,
-                    order,
-                    None,
-                )),
-                Err(e) => return Err(e),
+    // take a resting or untriggered order out of the book, along with a copy of the queue it
+    // was in so it can be put back exactly as it was
+    fn _take_order(&mut self, order_id: uuid::Uuid) -> Result<(Order, TakenFrom), OrderError> {
+        if let Some(order) = self.trigger_book.orders.get(&order_id).cloned() {
+            let triggers = match order.order_side {
+                OrderSide::BID => &self.trigger_book.bid_triggers,
+                OrderSide::ASK => &self.trigger_book.ask_triggers,
+            };
+            let queue = triggers
+                .get(&order.stop_price.unwrap())
+                .cloned()
+                .unwrap_or_default();
+            self.trigger_book.remove_order(order_id);
+            return Ok((order, TakenFrom::TRIGGERS(queue)));
+        }
+
+        let order = match self.oid_map.get(&order_id) {
+            Some(order) => order.clone(),
+            None => return Err(OrderError::InvalidOrderID),
+        };
+        let price_level = match self.get_price_level(order.order_side, order.price.unwrap()) {
+            Some(price_level) => price_level.clone(),
+            None => return Err(OrderError::InvalidPrice),
+        };
+        self._delete_order(order_id)?;
+        Ok((order, TakenFrom::LEVEL(price_level)))
+    }
+
+    // undo _take_order
+    fn _restore_order(&mut self, order: Order, taken_from: TakenFrom) {
+        match taken_from {
+            TakenFrom::LEVEL(price_level) => {
+                let price_levels = match order.order_side {
+                    OrderSide::BID => &mut self.bid_price_levels,
+                    OrderSide::ASK => &mut self.ask_price_levels,
+                };
+                price_levels.insert(price_level.price, price_level);
+                self.oid_map.insert(order.order_id, order);
+            }
+            TakenFrom::TRIGGERS(queue) => {
+                let triggers = match order.order_side {
+                    OrderSide::BID => &mut self.trigger_book.bid_triggers,
+                    OrderSide::ASK => &mut self.trigger_book.ask_triggers,
+                };
+                triggers.insert(order.stop_price.unwrap(), queue);
+                self.trigger_book.orders.insert(order.order_id, order);
             }
-        } else {
-            Ok(Execution::new(
-                ExecutionType::MATCH,
-                order.creator_id,
-                order.time_created,
-                order,
-                Some(order_to_match),
-            ))
         }
     }
 
-    // executes order from queue (matches order)
-    pub fn execute_order(&mut self) -> Result<Execution, OrderError> {
-        // get order from queue
-        let order: Order = match self.order_queue.pop_front() {
+    // reduce the visible qty of a resting order in place, keeping its queue position,
+    // unless it's an iceberg whose slice ran out, which is replenished at the back of the queue
+    fn _reduce_order(&mut self, order_id: uuid::Uuid, qty: i32) -> Result<(), OrderError> {
+        let order = match self.oid_map.get_mut(&order_id) {
             Some(order) => order,
-            None => return Err(OrderError::OrderQueueEmpty),
+            None => return Err(OrderError::InvalidOrderID),
         };
+        order.qty -= qty;
+        let replenished = order.visible_qty() == 0;
+        if replenished {
+            order.replenish();
+        }
+        let (order_side, price, visible_qty) =
+            (order.order_side, order.price.unwrap(), order.visible_qty());
 
-        // attempt to match order
-        match self.match_order(order) {
-            Ok(exec) => Ok(exec),
-            Err(e) => return Err(e),
+        match self.get_price_level(order_side, price) {
+            Some(price_level) => {
+                price_level.qty -= qty;
+                if replenished {
+                    price_level.orders.retain(|&x| x != order_id);
+                    price_level.orders.push_back(order_id);
+                    price_level.qty += visible_qty;
+                }
+                Ok(())
+            }
+            None => Err(OrderError::InvalidPrice),
         }
     }
 
-    // execute all orders in queue until empty, return a vector of executions in a result
-    pub fn execute_all_orders(&mut self) -> Result<Vec<Execution>, OrderError> {
-        let mut executions: Vec<Execution> = Vec::new();
+    // trade qty between the incoming order and a resting order, returning the fill
+    fn _fill(
+        &mut self,
+        order: &mut Order,
+        order_to_match: &Order,
+        price: Price,
+        trade_qty: i32,
+    ) -> Result<Fill, OrderError> {
+        order.qty -= trade_qty;
+
+        if order_to_match.qty > trade_qty {
+            // resting order is only partially filled, it keeps its place in the queue
+            self._reduce_order(order_to_match.order_id, trade_qty)?;
+        } else {
+            // resting order is fully filled, remove it from the book
+            self._delete_order(order_to_match.order_id)?;
+        }
 
-        loop {
-            match self.execute_order() {
-                Ok(exec) => {
-                    executions.push(exec);
-                }
-                Err(e) => {
-                    if e == OrderError::OrderQueueEmpty {
-                        break;
-                    } else {
-                        return Err(e);
-                    }
-                }
-            }
+        // set last market price to the resting order's price
+        self.last_market_price = Some(price);
+        let fill = Fill::new(order, order_to_match, price, trade_qty);
+
+        // the new last price may release stop orders
+        self._release_triggered_orders(price, self.clock.now());
+
+        Ok(fill)
+    }
+
+    // move stop orders triggered by the last price into the order queue
+    fn _release_triggered_orders(&mut self, last_price: Price, time_triggered: u32) {
+        self.trigger_book
+            .trail(last_price, self.stock_info.tick_size);
+        for mut order in self.trigger_book.take_triggered(last_price) {
+            order.activate();
+            self.events.push_back(Execution::new(
+                ExecutionType::TRIGGER,
+                order.creator_id,
+                time_triggered,
+                order.clone(),
+                Vec::new(),
+            ));
+            self.order_queue.push_back(order);
         }
+    }
 
-        Ok(executions)
+    // take the events produced as a side effect of matching since the last call
+    pub fn drain_events(&mut self) -> Vec<Execution> {
+        let mut events: Vec<Execution> = self.events.drain(..).collect();
+        for event in events.iter_mut() {
+            self.stamp(event);
+        }
+        events
     }
 
-    // print orderbook, with asks and bids side by side in a table, along with quantities at each price level
-    pub fn print_orderbook(&self) {
-        let mut table = Table::new();
-        table.add_row(row!["BID", "ASK"]);
-        let mut bid_it = self.bid_price_levels.iter().rev();
-        let mut ask_it = self.ask_price_levels.iter();
-        loop {
-            let bid = bid_it.next();
-            let ask = ask_it.next();
-            match (bid, ask) {
-                (Some(bid), Some(ask)) => {
-                    table.add_row(row![
-                        format!("{} @ {}", bid.1.qty, bid.0),
-                        format!("{} @ {}", ask.1.qty, ask.0)
-                    ]);
-                }
-                (Some(bid), None) => {
-                    table.add_row(row![format!("{} @ {}", bid.1.qty, bid.0), ""]);
-                }
-                (None, Some(ask)) => {
-                    table.add_row(row!["", format!("{} @ {}", ask.1.qty, ask.0)]);
-                }
-                (None, None) => {
-                    break;
-                }
-            }
+    // give an execution the book's next sequence number and the current engine time,
+    // executions are stamped once, on their way out of the book
+    pub fn stamp(&mut self, execution: &mut Execution) {
+        if execution.seq_num != 0 {
+            return;
         }
-        table.printstd();
+        self.seq_num += 1;
+        execution.seq_num = self.seq_num;
+        execution.engine_time = self.clock.now_nanos();
     }
-}
 
-impl User {
-    // create new user
-    pub fn new(
-        user_id: uuid::Uuid,
-        name: String,
-        email: String,
-        password: String,
-        balance: Option<f32>,
-    ) -> Self {
-        let user: User = User {
-            user_id,
-            name,
-            email,
-            password,
-            balance,
+    // worst price a market order with protection may trade at, from the current touch
+    fn _protection_limit(&self, order: &Order) -> Option<Price> {
+        let tick_size = self.stock_info.tick_size;
+        let touch = match order.order_side {
+            OrderSide::BID => self.best_ask()?,
+            OrderSide::ASK => self.best_bid()?,
         };
+        let slippage = match order.market_protection? {
+            MarketProtection::TICKS(ticks) => {
+                Price::new(tick_size.ticks.checked_mul(ticks as i64)?, tick_size.scale)
+            }
+            MarketProtection::BASIS_POINTS(bps) => {
+                Price::new(touch.ticks.checked_mul(bps as i64)? / 10000, touch.scale)
+            }
+        };
+        match order.order_side {
+            OrderSide::BID => touch.checked_add(slippage)?.floor_to(tick_size),
+            OrderSide::ASK => touch.checked_sub(slippage)?.ceil_to(tick_size),
+        }
+    }
 
-        user
+    // whether the order may trade at a price, given its limit and any protection limit
+    fn _can_trade_at(order: &Order, price: Price, protection_limit: Option<Price>) -> bool {
+        let within_protection = match (order.order_side, protection_limit) {
+            (OrderSide::BID, Some(limit)) => price <= limit,
+            (OrderSide::ASK, Some(limit)) => price >= limit,
+            (_, None) => true,
+        };
+        within_protection && order.crosses(price)
     }
-}
 
-impl UserStocks {
-    // create new user_stocks
-    pub fn new(user_id: uuid::Uuid, stock_id: uuid::Uuid, quantity: i32) -> Self {
+    // qty resting on the opposite side at prices the order is willing to trade at,
+    // including the hidden reserve of iceberg orders
+    fn _available_qty(&self, order: &Order, protection_limit: Option<Price>) -> i32 {
+        let price_levels: Box<dyn Iterator<Item = &PriceLevel>> = match order.order_side {
+            OrderSide::BID => Box::new(self.ask_price_levels.values()),
+            OrderSide::ASK => Box::new(self.bid_price_levels.values().rev()),
+        };
+
+        let resting_orders = price_levels
+            .take_while(|p_level| Self::_can_trade_at(order, p_level.price, protection_limit))
+            .flat_map(|p_level| p_level.orders.iter())
+            .filter_map(|order_id| self.oid_map.get(order_id))
+            .filter(|resting| {
+                order.self_trade_prevention.is_none() || resting.creator_id != order.creator_id
+            });
+
+        // walk the resting orders the way matching would, skipping the ones whose
+        // minimum qty constraints can't be met, up to the order's qty
+        let mut available: i32 = 0;
+        for resting in resting_orders {
+            let trade_qty = (order.qty - available).min(resting.qty);
+            if trade_qty == 0 {
+                break;
+            }
+            if resting.accepts_fill(trade_qty) && order.meets_min_qty(trade_qty) {
+                available += trade_qty;
+            }
+        }
+        available
+    }
+
+    // whether the order's self-trade prevention would cut it short at one of its creator's
+    // resting orders within its price range. only cancelling the resting order lets it carry on
+    fn _meets_own_order(&self, order: &Order, protection_limit: Option<Price>) -> bool {
+        if matches!(
+            order.self_trade_prevention,
+            None | Some(SelfTradePrevention::CANCEL_OLDEST)
+        ) {
+            return false;
+        }
+        let price_levels: Box<dyn Iterator<Item = &PriceLevel>> = match order.order_side {
+            OrderSide::BID => Box::new(self.ask_price_levels.values()),
+            OrderSide::ASK => Box::new(self.bid_price_levels.values().rev()),
+        };
+
+        price_levels
+            .take_while(|p_level| Self::_can_trade_at(order, p_level.price, protection_limit))
+            .flat_map(|p_level| p_level.orders.iter())
+            .filter_map(|order_id| self.oid_map.get(order_id))
+            .any(|resting| resting.creator_id == order.creator_id)
+    }
+
+    // cancel a resting order on behalf of self-trade prevention, notifying its owner
+    fn _cancel_resting_order(
+        &mut self,
+        resting: &Order,
+        time_cancelled: u32,
+    ) -> Result<(), OrderError> {
+        self._delete_order(resting.order_id)?;
+        self.events.push_back(Execution::new(
+            ExecutionType::CANCEL,
+            resting.creator_id,
+            time_cancelled,
+            resting.clone(),
+            Vec::new(),
+        ));
+        Ok(())
+    }
+
+    // next price level on the opposite side of an incoming order, strictly behind the given price
+    fn _next_price_level(&self, order_side: OrderSide, after: Option<Price>) -> Option<PriceLevel> {
+        match (order_side, after) {
+            (OrderSide::BID, None) => self.ask_price_levels.values().next().cloned(),
+            (OrderSide::BID, Some(price)) => self
+                .ask_price_levels
+                .range((Bound::Excluded(price), Bound::Unbounded))
+                .map(|(_, p_level)| p_level.clone())
+                .next(),
+            (OrderSide::ASK, None) => self.bid_price_levels.values().next_back().cloned(),
+            (OrderSide::ASK, Some(price)) => self
+                .bid_price_levels
+                .range(..price)
+                .map(|(_, p_level)| p_level.clone())
+                .next_back(),
+        }
+    }
+
+    // apply the incoming order's self-trade prevention against one of the creator's resting orders,
+    // returns whether the incoming order is cancelled
+    fn _prevent_self_trade(
+        &mut self,
+        order: &mut Order,
+        resting: &Order,
+        prevented_qty: i32,
+    ) -> Result<bool, OrderError> {
+        match order.self_trade_prevention {
+            Some(SelfTradePrevention::CANCEL_NEWEST) => Ok(true),
+            Some(SelfTradePrevention::CANCEL_OLDEST) => {
+                self._cancel_resting_order(resting, self.clock.now())?;
+                Ok(false)
+            }
+            Some(SelfTradePrevention::CANCEL_BOTH) => {
+                self._cancel_resting_order(resting, self.clock.now())?;
+                Ok(true)
+            }
+            Some(SelfTradePrevention::DECREMENT_AND_CANCEL) => {
+                order.qty -= prevented_qty;
+                if resting.qty > prevented_qty {
+                    // the resting order's owner hears about the decrement as a MODIFY
+                    self._reduce_order(resting.order_id, prevented_qty)?;
+                    let decremented = self.oid_map[&resting.order_id].clone();
+                    self.events.push_back(Execution::new(
+                        ExecutionType::MODIFY,
+                        decremented.creator_id,
+                        self.clock.now(),
+                        decremented,
+                        Vec::new(),
+                    ));
+                } else {
+                    self._cancel_resting_order(resting, self.clock.now())?;
+                }
+                Ok(order.qty == 0)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // match order against orderbook given order id in orderbook,
+    // price levels are walked from the best price outwards
+    pub fn match_order(&mut self, order: Order) -> Result<Execution, OrderError> {
+        let mut execution = self._match_order(order)?;
+        self.stamp(&mut execution);
+        // the top of book may have moved
+        self._reprice_pegged_orders();
+        Ok(execution)
+    }
+
+    fn _match_order(&mut self, mut order: Order) -> Result<Execution, OrderError> {
+        self._set_day_expiry(&mut order);
+
+        // orders that expired before reaching the book never trade
+        if order.is_expired(self.clock.now()) {
+            return Err(OrderError::OrderExpired);
+        }
+
+        // trailing stops without a stop price start trailing from the last price
+        if order.is_stop() && order.stop_price.is_none() {
+            let stop_price = match self.last_market_price {
+                Some(last_price) => {
+                    order.trailing_stop_price(last_price, self.stock_info.tick_size)
+                }
+                None => None,
+            };
+            match stop_price {
+                Some(stop_price) => order.stop_price = Some(stop_price),
+                None => return Err(OrderError::InvalidStopPrice),
+            }
+        }
+
+        // pegged orders take their price from the top of book
+        if order.peg.is_some() {
+            match self._peg_price(&order) {
+                Some(price) => order.price = Some(price),
+                None => return Err(OrderError::PegReferenceUnavailable),
+            }
+        }
+
+        // stop orders wait in the trigger book unless the last price is already through their stop
+        if order.is_stop() {
+            match self.last_market_price {
+                Some(last_price) if order.is_triggered(last_price) => {
+                    order.activate();
+                    self.events.push_back(Execution::new(
+                        ExecutionType::TRIGGER,
+                        order.creator_id,
+                        self.clock.now(),
+                        order.clone(),
+                        Vec::new(),
+                    ));
+                }
+                _ => {
+                    self.trigger_book.add_order(order.clone());
+                    return Ok(Execution::new(
+                        ExecutionType::ADD,
+                        order.creator_id,
+                        self.clock.now(),
+                        order,
+                        Vec::new(),
+                    ));
+                }
+            }
+        }
+
+        // during an auction orders only accumulate
+        if self.trading_phase == TradingPhase::AUCTION {
+            return self._add_auction_order(order);
+        }
+
+        // post-only orders that would cross are rejected or repriced one tick away
+        if let Some(post_only) = order.post_only {
+            let best_opposite = match order.order_side {
+                OrderSide::BID => self.best_ask(),
+                OrderSide::ASK => self.best_bid(),
+            };
+            if let Some(best_price) = best_opposite.filter(|price| order.crosses(*price)) {
+                let repriced = match order.order_side {
+                    OrderSide::BID => best_price - self.stock_info.tick_size,
+                    OrderSide::ASK => best_price + self.stock_info.tick_size,
+                };
+                match post_only {
+                    PostOnly::REPRICE if repriced.is_positive() => order.price = Some(repriced),
+                    _ => return Err(OrderError::PostOnlyWouldCross),
+                }
+            }
+        }
+
+        // market orders can be limited to a number of ticks or a percentage past the touch
+        let protection_limit = match order.order_type {
+            OrderType::MARKET => self._protection_limit(&order),
+            _ => None,
+        };
+
+        // fill-or-kill orders are rejected before touching the book if they can't fill in full,
+        // which they can't be sure of if self-trade prevention may stop them part way
+        let fills_in_full = self._available_qty(&order, protection_limit) == order.qty;
+        if order.time_in_force == TimeInForce::FOK
+            && (!fills_in_full || self._meets_own_order(&order, protection_limit))
+        {
+            return Err(OrderError::InsufficientLiquidity);
+        }
+        // all-or-none orders that can't fill in full don't trade on arrival
+        let can_trade = !order.all_or_none || fills_in_full;
+
+        let opposite_side = match order.order_side {
+            OrderSide::BID => OrderSide::ASK,
+            OrderSide::ASK => OrderSide::BID,
+        };
+        let mut fills: Vec<Fill> = Vec::new();
+        let mut last_level_price: Option<Price> = None;
+        let mut prevented_qty: i32 = 0;
+        let mut cancelled = false;
+
+        'walk: while can_trade && order.qty > 0 {
+            let p_level = match self._next_price_level(order.order_side, last_level_price) {
+                Some(p_level) => p_level,
+                None => break,
+            };
+            if !Self::_can_trade_at(&order, p_level.price, protection_limit) {
+                break;
+            }
+            last_level_price = Some(p_level.price);
+
+            // keep walking the level while it trades, replenished icebergs rejoin at the back
+            let mut level_orders: VecDeque<uuid::Uuid> = p_level.orders;
+            while !level_orders.is_empty() && order.qty > 0 {
+                // share the order out between the level's orders, orders whose minimum qty
+                // can't be met are skipped and keep their place
+                let resting: Vec<Order> = level_orders
+                    .iter()
+                    .filter_map(|order_id| self.oid_map.get(order_id))
+                    .cloned()
+                    .collect();
+                let allocations =
+                    self.policy
+                        .allocate(&self.stock_info, &order, &resting, order.qty);
+
+                let mut traded = false;
+                for (order_to_match_uuid, allocated_qty) in allocations {
+                    if order.qty == 0 {
+                        break;
+                    }
+
+                    // policies are free to return anything, orders that aren't resting at this
+                    // level are ignored
+                    if !level_orders.contains(&order_to_match_uuid) {
+                        continue;
+                    }
+                    let order_to_match = match self.oid_map.get(&order_to_match_uuid) {
+                        Some(order) => order.clone(),
+                        None => continue,
+                    };
+
+                    // earlier counterparties may have changed what's left to trade
+                    let trade_qty = allocated_qty
+                        .min(order.qty)
+                        .min(order_to_match.visible_qty());
+                    if trade_qty <= 0
+                        || !order_to_match.accepts_fill(trade_qty)
+                        || !order.meets_min_qty(trade_qty)
+                    {
+                        continue;
+                    }
+                    traded = true;
+
+                    // self-trade prevention, evaluated per counterparty
+                    if order.self_trade_prevention.is_some()
+                        && order_to_match.creator_id == order.creator_id
+                    {
+                        prevented_qty += trade_qty;
+                        if self._prevent_self_trade(&mut order, &order_to_match, trade_qty)? {
+                            cancelled = true;
+                            break 'walk;
+                        }
+                        // the level has changed, share out what's left again
+                        break;
+                    }
+
+                    let fill = self._fill(&mut order, &order_to_match, p_level.price, trade_qty)?;
+                    fills.push(fill);
+                }
+
+                // only skipped orders are left at this level, or the policy allocated nothing
+                if !traded {
+                    break;
+                }
+                level_orders = match self.get_price_level(opposite_side, p_level.price) {
+                    Some(p_level) => p_level.orders.clone(),
+                    None => VecDeque::new(),
+                };
+            }
+        }
+
+        // self-trade prevention, immediate-or-cancel, fill-or-kill and market orders drop
+        // whatever didn't fill
+        let drop_remainder = matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK)
+            || order.order_type == OrderType::MARKET;
+        let exec_type = if cancelled || (order.qty > 0 && drop_remainder) {
+            ExecutionType::CANCEL
+        } else if order.qty > 0 {
+            // add order to orderbook if it still has qty
+            self._add_order(order.clone())?;
+            ExecutionType::ADD
+        } else {
+            ExecutionType::MATCH
+        };
+
+        let mut execution =
+            Execution::new(exec_type, order.creator_id, self.clock.now(), order, fills);
+        execution.prevented_qty = prevented_qty;
+        Ok(execution)
+    }
+
+    // best price on a side ignoring pegged orders, so pegs never reference themselves
+    fn _unpegged_best(&self, order_side: OrderSide) -> Option<Price> {
+        let mut price_levels: Box<dyn Iterator<Item = &PriceLevel>> = match order_side {
+            OrderSide::BID => Box::new(self.bid_price_levels.values().rev()),
+            OrderSide::ASK => Box::new(self.ask_price_levels.values()),
+        };
+
+        price_levels
+            .find(|p_level| {
+                p_level
+                    .orders
+                    .iter()
+                    .filter_map(|order_id| self.oid_map.get(order_id))
+                    .any(|order| order.peg.is_none())
+            })
+            .map(|p_level| p_level.price)
+    }
+
+    // price a pegged order should currently rest at, kept one tick behind the opposite side so
+    // repricing never takes liquidity. None when the reference price doesn't exist
+    fn _peg_price(&self, order: &Order) -> Option<Price> {
+        let tick_size = self.stock_info.tick_size;
+        let (best_bid, best_ask) = (
+            self._unpegged_best(OrderSide::BID),
+            self._unpegged_best(OrderSide::ASK),
+        );
+        let (own_best, opposite_best) = match order.order_side {
+            OrderSide::BID => (best_bid, best_ask),
+            OrderSide::ASK => (best_ask, best_bid),
+        };
+        let reference = match order.peg? {
+            PegReference::PRIMARY => own_best?,
+            PegReference::MARKET => opposite_best?,
+            PegReference::MIDPOINT => own_best?.checked_add(opposite_best?)?.half()?,
+        };
+        let price = match order.peg_offset {
+            Some(peg_offset) => reference.checked_add(peg_offset)?,
+            None => reference,
+        };
+
+        // round away from the opposite side onto the tick grid, then keep off it
+        let (price, best_opposite) = match order.order_side {
+            OrderSide::BID => (price.floor_to(tick_size)?, self.best_ask()),
+            OrderSide::ASK => (price.ceil_to(tick_size)?, self.best_bid()),
+        };
+        let price = match (order.order_side, best_opposite) {
+            (OrderSide::BID, Some(best_ask)) if price >= best_ask => best_ask - tick_size,
+            (OrderSide::ASK, Some(best_bid)) if price <= best_bid => best_bid + tick_size,
+            _ => price,
+        };
+
+        price.is_positive().then_some(price)
+    }
+
+    // move pegged orders whose reference price changed, to the back of their new price level
+    fn _reprice_pegged_orders(&mut self) {
+        if self.trading_phase == TradingPhase::AUCTION {
+            return;
+        }
+
+        let pegged: Vec<Order> = self
+            .oid_map
+            .values()
+            .filter(|order| order.peg.is_some())
+            .cloned()
+            .collect();
+        for mut order in pegged {
+            let price = match self._peg_price(&order) {
+                Some(price) if Some(price) != order.price => price,
+                // keep the current price while the reference is missing
+                _ => continue,
+            };
+            if self._delete_order(order.order_id).is_err() {
+                continue;
+            }
+            order.price = Some(price);
+            if self._add_order(order.clone()).is_ok() {
+                self.events.push_back(Execution::new(
+                    ExecutionType::MODIFY,
+                    order.creator_id,
+                    self.clock.now(),
+                    order,
+                    Vec::new(),
+                ));
+            }
+        }
+    }
+
+    // use a different time source for expiring orders
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    // use a different rule for sharing fills between orders at the same price
+    pub fn set_policy(&mut self, policy: Arc<dyn MatchingPolicy>) {
+        self.policy = policy;
+    }
+
+    // remove every resting or untriggered order whose expiry has passed, returning an EXPIRE
+    // execution for each
+    pub fn expire_orders(&mut self) -> Vec<Execution> {
+        let now = self.clock.now();
+        let expired: Vec<Order> = self
+            .oid_map
+            .values()
+            .chain(self.trigger_book.orders.values())
+            .filter(|order| order.is_expired(now))
+            .cloned()
+            .collect();
+
+        let mut executions: Vec<Execution> = Vec::new();
+        for order in expired {
+            if self._delete_order(order.order_id).is_ok() {
+                executions.push(Execution::new(
+                    ExecutionType::EXPIRE,
+                    order.creator_id,
+                    now,
+                    order,
+                    Vec::new(),
+                ));
+            }
+        }
+        for execution in executions.iter_mut() {
+            self.stamp(execution);
+        }
+        self._reprice_pegged_orders();
+
+        executions
+    }
+
+    // switch the book into call-auction mode, orders rest without matching until uncross()
+    pub fn start_auction(&mut self) {
+        self.trading_phase = TradingPhase::AUCTION;
+    }
+
+    // rest an order during an auction and report the new indicative state
+    fn _add_auction_order(&mut self, order: Order) -> Result<Execution, OrderError> {
+        // nothing can execute immediately during an auction, and market orders have no price
+        // to rest at
+        if matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK) {
+            return Err(OrderError::InvalidTimeInForce);
+        }
+        if order.order_type == OrderType::MARKET {
+            return Err(OrderError::InvalidPrice);
+        }
+        self._add_order(order.clone())?;
+
+        let mut execution = Execution::new(
+            ExecutionType::ADD,
+            order.creator_id,
+            self.clock.now(),
+            order,
+            Vec::new(),
+        );
+        execution.auction = Some(self.auction_state());
+        Ok(execution)
+    }
+
+    // resting orders on one side in price-time priority (best price first), including hidden qty
+    fn _priority_orders(&self, order_side: OrderSide) -> Vec<Order> {
+        let price_levels: Box<dyn Iterator<Item = &PriceLevel>> = match order_side {
+            OrderSide::BID => Box::new(self.bid_price_levels.values().rev()),
+            OrderSide::ASK => Box::new(self.ask_price_levels.values()),
+        };
+
+        price_levels
+            .flat_map(|p_level| p_level.orders.iter())
+            .filter_map(|order_id| self.oid_map.get(order_id))
+            .cloned()
+            .collect()
+    }
+
+    // indicative auction state: the price that maximizes executed volume, then minimizes the
+    // imbalance, then is closest to the last market price, then is lowest
+    pub fn auction_state(&self) -> AuctionState {
+        let candidate_prices = self
+            .bid_price_levels
+            .keys()
+            .chain(self.ask_price_levels.keys());
+
+        let mut best: Option<(Price, i32, i32)> = None;
+        for &price in candidate_prices {
+            let (bids, asks, fills) = self._auction_pairing(price);
+            let demand: i32 = bids.iter().map(|bid| bid.qty).sum();
+            let supply: i32 = asks.iter().map(|ask| ask.qty).sum();
+            let volume: i32 = fills.iter().map(|fill| fill.qty).sum();
+            let imbalance = demand - supply;
+            if volume == 0 {
+                continue;
+            }
+
+            let better = match best {
+                None => true,
+                Some((best_price, best_volume, best_imbalance)) => {
+                    let distance = |p: Price| match self.last_market_price {
+                        Some(last_price) if p > last_price => p - last_price,
+                        Some(last_price) => last_price - p,
+                        None => Price::new(0, 0),
+                    };
+                    (volume, -imbalance.abs(), distance(best_price), best_price)
+                        > (best_volume, -best_imbalance.abs(), distance(price), price)
+                }
+            };
+            if better {
+                best = Some((price, volume, imbalance));
+            }
+        }
+
+        match best {
+            Some((price, volume, imbalance)) => AuctionState {
+                price: Some(price),
+                volume,
+                imbalance: imbalance.abs(),
+                imbalance_side: match imbalance {
+                    i if i > 0 => Some(OrderSide::BID),
+                    i if i < 0 => Some(OrderSide::ASK),
+                    _ => None,
+                },
+            },
+            None => AuctionState {
+                price: None,
+                volume: 0,
+                imbalance: 0,
+                imbalance_side: None,
+            },
+        }
+    }
+
+    // pair off the orders that would trade at an uncross price in price-time priority. orders
+    // whose all-or-none or minimum qty their share can't meet are left out, which may leave
+    // others short in turn, so pairing repeats until every order left is satisfied
+    fn _auction_pairing(&self, price: Price) -> (Vec<Order>, Vec<Order>, Vec<Fill>) {
+        let mut excluded: BTreeSet<uuid::Uuid> = BTreeSet::new();
+
+        loop {
+            let bids: Vec<Order> = self
+                ._priority_orders(OrderSide::BID)
+                .into_iter()
+                .filter(|bid| bid.price.unwrap() >= price && !excluded.contains(&bid.order_id))
+                .collect();
+            let asks: Vec<Order> = self
+                ._priority_orders(OrderSide::ASK)
+                .into_iter()
+                .filter(|ask| ask.price.unwrap() <= price && !excluded.contains(&ask.order_id))
+                .collect();
+
+            let mut fills: Vec<Fill> = Vec::new();
+            let (mut bid_left, mut ask_left) = (
+                bids.first().map_or(0, |bid| bid.qty),
+                asks.first().map_or(0, |ask| ask.qty),
+            );
+            let (mut i, mut j) = (0, 0);
+            while i < bids.len() && j < asks.len() {
+                let trade_qty = bid_left.min(ask_left);
+                fills.push(Fill::new(&bids[i], &asks[j], price, trade_qty));
+                bid_left -= trade_qty;
+                ask_left -= trade_qty;
+                if bid_left == 0 {
+                    i += 1;
+                    bid_left = bids.get(i).map_or(0, |bid| bid.qty);
+                }
+                if ask_left == 0 {
+                    j += 1;
+                    ask_left = asks.get(j).map_or(0, |ask| ask.qty);
+                }
+            }
+
+            let mut traded: BTreeMap<uuid::Uuid, i32> = BTreeMap::new();
+            for fill in fills.iter() {
+                *traded.entry(fill.taker_order_id).or_insert(0) += fill.qty;
+                *traded.entry(fill.maker_order_id).or_insert(0) += fill.qty;
+            }
+            let unmet: Vec<uuid::Uuid> = bids
+                .iter()
+                .chain(asks.iter())
+                .filter(|order| {
+                    traded
+                        .get(&order.order_id)
+                        .is_some_and(|&qty| !order.accepts_fill(qty))
+                })
+                .map(|order| order.order_id)
+                .collect();
+            if unmet.is_empty() {
+                return (bids, asks, fills);
+            }
+            excluded.extend(unmet);
+        }
+    }
+
+    // take qty out of a resting order regardless of how much of it is shown
+    fn _consume_qty(&mut self, order_id: uuid::Uuid, qty: i32) -> Result<(), OrderError> {
+        let order = match self.oid_map.get(&order_id) {
+            Some(order) => order.clone(),
+            None => return Err(OrderError::InvalidOrderID),
+        };
+
+        if qty >= order.qty {
+            self._delete_order(order_id)
+        } else if qty <= order.visible_qty() {
+            self._reduce_order(order_id, qty)
+        } else {
+            // ate into an iceberg's reserve, show a new slice at the back of the queue
+            self._modify_order(order_id, order.qty - qty, order.price)
+        }
+    }
+
+    // take qty out of a resting or untriggered order without changing its price, deleting it
+    // once nothing is left
+    pub fn reduce_order(&mut self, order_id: uuid::Uuid, qty: i32) -> Result<(), OrderError> {
+        if let Some(mut order) = self.trigger_book.remove_order(order_id) {
+            order.qty -= qty;
+            if order.qty > 0 {
+                self.trigger_book.add_order(order);
+            }
+            return Ok(());
+        }
+
+        self._consume_qty(order_id, qty)
+    }
+
+    // end the auction: execute every crossing order at the single clearing price, in price-time
+    // priority, and return to continuous matching. One UNCROSS execution is reported per bid
+    // that traded, with the bid as the nominal taker of its fills
+    pub fn uncross(&mut self) -> Result<Vec<Execution>, OrderError> {
+        let state = self.auction_state();
+        self.trading_phase = TradingPhase::CONTINUOUS;
+        let clearing_price = match state.price {
+            Some(price) => price,
+            None => return Ok(Vec::new()),
+        };
+
+        // pair off both sides, keeping track of what each order traded
+        let (bids, _, fills) = self._auction_pairing(clearing_price);
+
+        let mut traded: BTreeMap<uuid::Uuid, i32> = BTreeMap::new();
+        for fill in fills.iter() {
+            *traded.entry(fill.taker_order_id).or_insert(0) += fill.qty;
+            *traded.entry(fill.maker_order_id).or_insert(0) += fill.qty;
+        }
+        for (order_id, qty) in traded.iter() {
+            self._consume_qty(*order_id, *qty)?;
+        }
+        self.last_market_price = Some(clearing_price);
+
+        let time_executed = self.clock.now();
+        let mut executions: Vec<Execution> = Vec::new();
+        for mut bid in bids
+            .into_iter()
+            .filter(|bid| traded.contains_key(&bid.order_id))
+        {
+            bid.qty -= traded[&bid.order_id];
+            let bid_fills: Vec<Fill> = fills
+                .iter()
+                .filter(|fill| fill.taker_order_id == bid.order_id)
+                .cloned()
+                .collect();
+            executions.push(Execution::new(
+                ExecutionType::UNCROSS,
+                bid.creator_id,
+                time_executed,
+                bid,
+                bid_fills,
+            ));
+        }
+        for execution in executions.iter_mut() {
+            self.stamp(execution);
+        }
+
+        // the clearing price may release stop orders
+        self._release_triggered_orders(clearing_price, time_executed);
+        self._reprice_pegged_orders();
+
+        Ok(executions)
+    }
+
+    // executes order from queue (matches order)
+    pub fn execute_order(&mut self) -> Result<Execution, OrderError> {
+        // get order from queue
+        let order: Order = match self.order_queue.pop_front() {
+            Some(order) => order,
+            None => return Err(OrderError::OrderQueueEmpty),
+        };
+
+        // attempt to match order
+        self.match_order(order)
+    }
+
+    // execute all orders in queue until empty, return a vector of executions in a result
+    pub fn execute_all_orders(&mut self) -> Result<Vec<Execution>, OrderError> {
+        let mut executions: Vec<Execution> = Vec::new();
+
+        loop {
+            match self.execute_order() {
+                Ok(exec) => {
+                    executions.push(exec);
+                    // followed by any events it caused, e.g. stop triggers
+                    executions.extend(self.drain_events());
+                }
+                Err(e) => {
+                    if e == OrderError::OrderQueueEmpty {
+                        break;
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(executions)
+    }
+
+    // match stops released by earlier trades, each followed by any events it caused. A stop
+    // that can no longer be matched is cancelled
+    pub fn execute_triggered_orders(&mut self) -> Vec<Execution> {
+        let mut executions: Vec<Execution> = Vec::new();
+
+        while let Some(order) = self.order_queue.pop_front() {
+            match self.match_order(order.clone()) {
+                Ok(exec) => executions.push(exec),
+                Err(_) => {
+                    let mut exec = Execution::new(
+                        ExecutionType::CANCEL,
+                        order.creator_id,
+                        self.clock.now(),
+                        order,
+                        Vec::new(),
+                    );
+                    self.stamp(&mut exec);
+                    executions.push(exec);
+                }
+            }
+            executions.extend(self.drain_events());
+        }
+
+        executions
+    }
+
+    // print orderbook, with asks and bids side by side in a table, along with quantities at each price level,
+    // both sides start at the top of the book
+    pub fn print_orderbook(&self) {
+        let mut table = Table::new();
+        table.add_row(row!["BID", "ASK"]);
+        let mut bid_it = self.bid_price_levels.iter().rev();
+        let mut ask_it = self.ask_price_levels.iter();
+        loop {
+            let bid = bid_it.next();
+            let ask = ask_it.next();
+            match (bid, ask) {
+                (Some(bid), Some(ask)) => {
+                    table.add_row(row![
+                        format!("{} @ {}", bid.1.qty, bid.0),
+                        format!("{} @ {}", ask.1.qty, ask.0)
+                    ]);
+                }
+                (Some(bid), None) => {
+                    table.add_row(row![format!("{} @ {}", bid.1.qty, bid.0), ""]);
+                }
+                (None, Some(ask)) => {
+                    table.add_row(row!["", format!("{} @ {}", ask.1.qty, ask.0)]);
+                }
+                (None, None) => {
+                    break;
+                }
+            }
+        }
+        table.printstd();
+    }
+}
+
+impl User {
+    // create new user
+    pub fn new(
+        user_id: uuid::Uuid,
+        name: String,
+        email: String,
+        password: String,
+        balance: Option<f32>,
+    ) -> Self {
+        let user: User = User {
+            user_id,
+            name,
+            email,
+            password,
+            balance,
+        };
+
+        user
+    }
+}
+
+impl UserStocks {
+    // create new user_stocks
+    pub fn new(user_id: uuid::Uuid, stock_id: uuid::Uuid, quantity: i32) -> Self {
         let user_stocks: UserStocks = UserStocks {
             user_id,
             stock_id,
@@ -801,6 +2764,106 @@ impl UserStocks {
     }
 }
 
+impl Default for Exchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderStatus {
+    // whether the order is done with, i.e. can no longer trade
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::FILLED
+                | OrderStatus::CANCELLED
+                | OrderStatus::EXPIRED
+                | OrderStatus::REJECTED
+        )
+    }
+}
+
+impl CancelFilter {
+    pub fn matches(&self, order: &Order) -> bool {
+        self.creator_id.is_none_or(|id| id == order.creator_id)
+            && self.stock_id.is_none_or(|id| id == order.stock.stock_id)
+            && self.order_side.is_none_or(|side| side == order.order_side)
+    }
+}
+
+impl Session {
+    // create new session
+    pub fn new(
+        session_id: uuid::Uuid,
+        creator_id: uuid::Uuid,
+        stock_id: Option<uuid::Uuid>,
+        order_side: Option<OrderSide>,
+        cancel_on_disconnect: bool,
+    ) -> Self {
+        let session: Session = Session {
+            session_id,
+            creator_id,
+            stock_id,
+            order_side,
+            cancel_on_disconnect,
+        };
+
+        session
+    }
+
+    // the orders the session covers
+    pub fn filter(&self) -> CancelFilter {
+        CancelFilter {
+            creator_id: Some(self.creator_id),
+            stock_id: self.stock_id,
+            order_side: self.order_side,
+        }
+    }
+}
+
+impl OrderState {
+    // state of an order the exchange has just seen
+    pub fn new(order: Order, time_updated: u32) -> Self {
+        let filled_notional = Price::new(0, order.stock.price_scale);
+        OrderState {
+            order,
+            status: OrderStatus::NEW,
+            filled_qty: 0,
+            filled_notional,
+            time_updated,
+        }
+    }
+
+    // volume weighted price over all fills
+    pub fn avg_price(&self) -> Option<f64> {
+        match self.filled_qty {
+            0 => None,
+            qty => Some(self.filled_notional.to_f64() / qty as f64),
+        }
+    }
+
+    // record a fill against the order
+    fn _fill(&mut self, price: Price, qty: i32) {
+        self.filled_qty += qty;
+        // the total saturates rather than wrapping, only the average price is derived from it
+        self.filled_notional = price
+            .notional(qty)
+            .and_then(|notional| self.filled_notional.checked_add(notional))
+            .unwrap_or(Price::new(i64::MAX, self.filled_notional.scale));
+    }
+
+    // status of an order that's still in play
+    fn _open_status(&self) -> OrderStatus {
+        if self.order.qty <= 0 {
+            OrderStatus::FILLED
+        } else if self.filled_qty > 0 {
+            OrderStatus::PARTIALLY_FILLED
+        } else {
+            OrderStatus::NEW
+        }
+    }
+}
+
 impl Exchange {
     // create new exchange
     pub fn new() -> Self {
@@ -809,6 +2872,15 @@ impl Exchange {
             stocks: BTreeMap::new(),
             user_stocks: BTreeMap::new(),
             orderbooks: BTreeMap::new(),
+            order_groups: BTreeMap::new(),
+            group_orders: BTreeMap::new(),
+            order_registry: BTreeMap::new(),
+            creator_orders: BTreeMap::new(),
+            order_stocks: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+            finished_orders: VecDeque::new(),
+            status_retention: default_status_retention(),
+            clock: clock::system_clock(),
         };
 
         exchange
@@ -817,16 +2889,15 @@ impl Exchange {
     // add stock to exchange
     pub fn add_stock(&mut self, stock: Stock, issuer: User) -> Result<(), StockError> {
         // check for duplicates
-        match self.stocks.get(&stock.stock_id) {
-            Some(_) => return Err(StockError::DuplicateStockID),
-            None => {}
+        if self.stocks.contains_key(&stock.stock_id) {
+            return Err(StockError::DuplicateStockID);
         }
 
         self.stocks.insert(stock.clone().stock_id, stock.clone());
-        self.orderbooks.insert(
-            stock.clone().stock_id.to_string(),
-            OrderBook::new(stock.clone()),
-        );
+        let mut orderbook = OrderBook::new(stock.clone());
+        orderbook.set_clock(self.clock.clone());
+        self.orderbooks
+            .insert(stock.clone().stock_id.to_string(), orderbook);
         self.users.insert(issuer.clone().user_id, issuer.clone());
 
         // add stock to user_stocks
@@ -867,72 +2938,768 @@ impl Exchange {
             None => return Err(OrderError::InvalidStockID),
         };
 
-        // queue order
-        orderbook.queue_order(order.clone());
+        // reject invalid orders and orders that break the stock's tick/lot/size constraints
+        // before they reach the book. the order is validated first, so the constraints are
+        // only ever checked against prices at the stock's scale
+        let valid = order
+            .validate()
+            .and_then(|_| orderbook.stock_info.validate_order(&order));
+        if let Err(e) = valid {
+            self._track_rejection(&order);
+            return Err(e);
+        }
+
+        // match the order itself, not whatever is at the front of the book's queue
+        let exec = match orderbook.match_order(order.clone()) {
+            Ok(exec) => exec,
+            Err(e) => {
+                self._track_rejection(&order);
+                return Err(e);
+            }
+        };
+        self._settle_order_groups(order.stock.stock_id, std::slice::from_ref(&exec));
+
+        Ok(exec)
+    }
+
+    // carry fills and cancellations over to any order groups involved, leaving the
+    // resulting events with the book's own for the caller to drain
+    fn _settle_order_groups(&mut self, stock_id: uuid::Uuid, execs: &[Execution]) {
+        let mut events: Vec<Execution> = Vec::new();
+        let mut pending: Vec<Execution> = execs.to_vec();
+
+        // stops released along the way are matched straight away, until the book settles
+        loop {
+            let orderbook = match self.orderbooks.get_mut(&stock_id.to_string()) {
+                Some(orderbook) => orderbook,
+                None => return,
+            };
+            let mut released = orderbook.drain_events();
+            released.extend(orderbook.execute_triggered_orders());
+            pending.extend(released.iter().cloned());
+            events.extend(released);
+            if pending.is_empty() {
+                break;
+            }
+            let generated = self._update_order_groups(stock_id, std::mem::take(&mut pending));
+            events.extend(generated);
+        }
 
-        // execute order
-        match orderbook.execute_order() {
-            Ok(exec) => Ok(exec),
-            Err(e) => Err(e),
+        let mut executions = execs.to_vec();
+        executions.extend(events.iter().cloned());
+        self._track_executions(&executions);
+        if let Some(orderbook) = self.orderbooks.get_mut(&stock_id.to_string()) {
+            orderbook.events.extend(events);
         }
     }
 
-    // execute all orders (cleanup) and return a vector of executions
+    // execute all orders (cleanup) and return a vector of executions. each queued order is
+    // handled on its own, one that can't be matched is reported as a REJECT and the rest
+    // still execute
     pub fn execute_all_orders(&mut self) -> Result<Vec<Execution>, OrderError> {
         let mut executions: Vec<Execution> = Vec::new();
 
-        for (_, orderbook) in self.orderbooks.iter_mut() {
-            loop {
-                match orderbook.execute_order() {
+        let stock_ids: Vec<uuid::Uuid> = self.orderbooks.values().map(|o| o.stock_id).collect();
+        for stock_id in stock_ids {
+            // take the batch up front, stops released along the way are queued behind it
+            // and matched when the book settles
+            let queued: Vec<Order> = match self.orderbooks.get_mut(&stock_id.to_string()) {
+                Some(orderbook) => orderbook.order_queue.drain(..).collect(),
+                None => continue,
+            };
+            for order in queued {
+                let orderbook = match self.orderbooks.get_mut(&stock_id.to_string()) {
+                    Some(orderbook) => orderbook,
+                    None => break,
+                };
+
+                match orderbook.match_order(order.clone()) {
                     Ok(exec) => {
+                        self._settle_order_groups(stock_id, std::slice::from_ref(&exec));
                         executions.push(exec);
                     }
                     Err(e) => {
-                        if e == OrderError::OrderQueueEmpty {
-                            break;
-                        } else {
-                            return Err(e);
-                        }
+                        let mut exec = Execution::reject(order.clone(), &e, self.clock.now());
+                        orderbook.stamp(&mut exec);
+                        self._track_rejection(&order);
+                        executions.push(exec);
+                    }
+                }
+                if let Some(orderbook) = self.orderbooks.get_mut(&stock_id.to_string()) {
+                    executions.extend(orderbook.drain_events());
+                }
+            }
+        }
+
+        Ok(executions)
+    }
+
+    // link two orders on the same stock so that a fill on one reduces the other by the same
+    // qty, cancelling it once nothing is left
+    pub fn submit_oco(
+        &mut self,
+        first: Order,
+        second: Order,
+    ) -> Result<Vec<Execution>, OrderError> {
+        let group = OrderGroup {
+            group_id: uuid::Uuid::new_v4(),
+            group_type: OrderGroupType::OCO,
+            stock_id: first.stock.stock_id,
+            entry: None,
+            entry_filled: 0,
+            legs: vec![first, second],
+            armed: true,
+        };
+        self._validate_order_group(&group)?;
+        self._submit_order_group(group)
+    }
+
+    // submit an entry order with a take-profit limit and a stop-loss on the other side, the
+    // exit legs are armed as an OCO pair once the entry has filled
+    pub fn submit_bracket(
+        &mut self,
+        entry: Order,
+        take_profit: Order,
+        stop_loss: Order,
+    ) -> Result<Vec<Execution>, OrderError> {
+        if take_profit.order_type != OrderType::LIMIT
+            || !stop_loss.is_stop()
+            || take_profit.order_side == entry.order_side
+            || stop_loss.order_side == entry.order_side
+        {
+            return Err(OrderError::InvalidOrderGroup);
+        }
+        let group = OrderGroup {
+            group_id: uuid::Uuid::new_v4(),
+            group_type: OrderGroupType::BRACKET,
+            stock_id: entry.stock.stock_id,
+            entry: Some(entry),
+            entry_filled: 0,
+            legs: vec![take_profit, stop_loss],
+            armed: false,
+        };
+        self._validate_order_group(&group)?;
+        self._submit_order_group(group)
+    }
+
+    pub fn get_order_group(&self, group_id: uuid::Uuid) -> Option<&OrderGroup> {
+        self.order_groups.get(&group_id)
+    }
+
+    // every order in a group must be valid, from the same creator and for the same stock
+    fn _validate_order_group(&self, group: &OrderGroup) -> Result<(), OrderError> {
+        let orderbook = match self.orderbooks.get(&group.stock_id.to_string()) {
+            Some(orderbook) => orderbook,
+            None => return Err(OrderError::InvalidStockID),
+        };
+
+        let orders: Vec<&Order> = group.entry.iter().chain(group.legs.iter()).collect();
+        for order in orders.iter() {
+            if order.stock.stock_id != group.stock_id || order.creator_id != orders[0].creator_id {
+                return Err(OrderError::InvalidOrderGroup);
+            }
+            if self.group_orders.contains_key(&order.order_id) {
+                return Err(OrderError::InvalidOrderGroup);
+            }
+            order.validate()?;
+            orderbook.stock_info.validate_order(order)?;
+        }
+
+        Ok(())
+    }
+
+    // register a group and send its entry, or its legs if it has none
+    fn _submit_order_group(&mut self, group: OrderGroup) -> Result<Vec<Execution>, OrderError> {
+        let group_id = group.group_id;
+        let orders: Vec<Order> = match group.entry.clone() {
+            Some(entry) => vec![entry],
+            None => group.legs.clone(),
+        };
+        for order in group.entry.iter().chain(group.legs.iter()) {
+            self.group_orders.insert(order.order_id, group_id);
+        }
+        self.order_groups.insert(group_id, group);
+
+        let mut executions: Vec<Execution> = Vec::new();
+        for order in orders {
+            // an earlier leg may have already filled against this one
+            let order = match self.order_groups.get(&group_id) {
+                Some(group) => match group.legs.iter().find(|l| l.order_id == order.order_id) {
+                    Some(leg) if leg.qty <= 0 => continue,
+                    Some(leg) => leg.clone(),
+                    None => order,
+                },
+                None => break,
+            };
+            let mut exec = self.execute_order(order)?;
+            exec.group_id = Some(group_id);
+            executions.push(exec);
+        }
+
+        Ok(executions)
+    }
+
+    // follow executions through to the order groups they touch, returning the executions
+    // produced by arming, reducing or cancelling group legs
+    fn _update_order_groups(
+        &mut self,
+        stock_id: uuid::Uuid,
+        executions: Vec<Execution>,
+    ) -> Vec<Execution> {
+        let mut generated: Vec<Execution> = Vec::new();
+        let mut pending: VecDeque<Execution> = executions.into();
+
+        while let Some(exec) = pending.pop_front() {
+            let mut events: Vec<Execution> = Vec::new();
+            for fill in exec.fills.iter() {
+                for order_id in [fill.taker_order_id, fill.maker_order_id] {
+                    if let Some(&group_id) = self.group_orders.get(&order_id) {
+                        events.extend(self._group_fill(stock_id, group_id, order_id, fill.qty));
+                    }
+                }
+            }
+
+            // a group order leaving the book without filling takes the rest of the group with it,
+            // except a bracket entry that partly filled, whose exits still cover what it bought
+            if matches!(
+                exec.exec_type,
+                ExecutionType::CANCEL | ExecutionType::EXPIRE | ExecutionType::DELETE
+            ) {
+                if let Some(&group_id) = self.group_orders.get(&exec.order.order_id) {
+                    let partial_entry = self.order_groups.get(&group_id).filter(|group| {
+                        group.entry_filled > 0
+                            && group
+                                .entry
+                                .as_ref()
+                                .is_some_and(|entry| entry.order_id == exec.order.order_id)
+                    });
+                    match partial_entry.cloned() {
+                        Some(group) => events.extend(self._arm_bracket(stock_id, group_id, group)),
+                        None => events.extend(self._cancel_order_group(stock_id, group_id)),
                     }
                 }
             }
+
+            pending.extend(events.iter().cloned());
+            generated.extend(events);
+        }
+
+        generated
+    }
+
+    // a fill on a group order: arm a bracket once its entry has filled, or reduce the other
+    // legs by the filled qty
+    fn _group_fill(
+        &mut self,
+        stock_id: uuid::Uuid,
+        group_id: uuid::Uuid,
+        order_id: uuid::Uuid,
+        qty: i32,
+    ) -> Vec<Execution> {
+        let mut events: Vec<Execution> = Vec::new();
+        let (mut group, orderbook) = match (
+            self.order_groups.get(&group_id).cloned(),
+            self.orderbooks.get_mut(&stock_id.to_string()),
+        ) {
+            (Some(group), Some(orderbook)) => (group, orderbook),
+            _ => return events,
+        };
+        let time_executed = orderbook.clock.now();
+
+        if let Some(entry) = group.entry.clone() {
+            if entry.order_id == order_id {
+                group.entry_filled += qty;
+                if group.entry_filled >= entry.qty {
+                    return self._arm_bracket(stock_id, group_id, group);
+                }
+                self.order_groups.insert(group_id, group);
+                return events;
+            }
+        }
+
+        for leg in group.legs.iter_mut() {
+            leg.qty -= qty;
+            if leg.order_id == order_id {
+                continue;
+            }
+            // legs that haven't reached the book yet are only reduced here
+            let live = orderbook.oid_map.contains_key(&leg.order_id)
+                || orderbook.trigger_book.contains(&leg.order_id);
+            if live && orderbook.reduce_order(leg.order_id, qty).is_err() {
+                leg.qty = 0;
+            }
+            let exec_type = match leg.qty {
+                q if q <= 0 => ExecutionType::CANCEL,
+                _ => ExecutionType::MODIFY,
+            };
+            let mut exec = Execution::new(
+                exec_type,
+                leg.creator_id,
+                time_executed,
+                leg.clone(),
+                Vec::new(),
+            );
+            exec.group_id = Some(group_id);
+            orderbook.stamp(&mut exec);
+            events.push(exec);
+        }
+
+        // the group is done once every leg is
+        for leg in group.legs.iter().filter(|leg| leg.qty <= 0) {
+            self.group_orders.remove(&leg.order_id);
+        }
+        if group.legs.iter().all(|leg| leg.qty <= 0) {
+            self.order_groups.remove(&group_id);
+        } else {
+            self.order_groups.insert(group_id, group);
+        }
+
+        events
+    }
+
+    // send a bracket's exit legs to the book once its entry is done, sized to what the entry
+    // filled
+    fn _arm_bracket(
+        &mut self,
+        stock_id: uuid::Uuid,
+        group_id: uuid::Uuid,
+        mut group: OrderGroup,
+    ) -> Vec<Execution> {
+        let mut events: Vec<Execution> = Vec::new();
+        let orderbook = match self.orderbooks.get_mut(&stock_id.to_string()) {
+            Some(orderbook) => orderbook,
+            None => return events,
+        };
+        let time_executed = orderbook.clock.now();
+
+        if let Some(entry) = group.entry.take() {
+            self.group_orders.remove(&entry.order_id);
+        }
+        group.armed = true;
+        for leg in group.legs.iter_mut() {
+            leg.qty = leg.qty.min(group.entry_filled);
+            match orderbook.match_order(leg.clone()) {
+                Ok(mut exec) => {
+                    exec.group_id = Some(group_id);
+                    events.push(exec);
+                }
+                Err(_) => {
+                    let mut exec = Execution::new(
+                        ExecutionType::CANCEL,
+                        leg.creator_id,
+                        time_executed,
+                        leg.clone(),
+                        Vec::new(),
+                    );
+                    exec.group_id = Some(group_id);
+                    orderbook.stamp(&mut exec);
+                    events.push(exec);
+                }
+            }
+            events.extend(orderbook.drain_events());
+        }
+        self.order_groups.insert(group_id, group);
+
+        events
+    }
+
+    // cancel every remaining order in a group
+    fn _cancel_order_group(
+        &mut self,
+        stock_id: uuid::Uuid,
+        group_id: uuid::Uuid,
+    ) -> Vec<Execution> {
+        let mut events: Vec<Execution> = Vec::new();
+        let (group, orderbook) = match (
+            self.order_groups.remove(&group_id),
+            self.orderbooks.get_mut(&stock_id.to_string()),
+        ) {
+            (Some(group), Some(orderbook)) => (group, orderbook),
+            _ => return events,
+        };
+        let time_executed = orderbook.clock.now();
+
+        for order in group.entry.iter().chain(group.legs.iter()) {
+            self.group_orders.remove(&order.order_id);
+            // orders still in the book are pulled, legs that were never armed just reported
+            let live = orderbook.oid_map.contains_key(&order.order_id)
+                || orderbook.trigger_book.contains(&order.order_id);
+            let unarmed_leg = !group.armed
+                && order.qty > 0
+                && group.legs.iter().any(|leg| leg.order_id == order.order_id);
+            if live {
+                let _ = orderbook.delete_order(order.order_id);
+            } else if !unarmed_leg {
+                continue;
+            }
+            let mut exec = Execution::new(
+                ExecutionType::CANCEL,
+                order.creator_id,
+                time_executed,
+                order.clone(),
+                Vec::new(),
+            );
+            exec.group_id = Some(group_id);
+            orderbook.stamp(&mut exec);
+            events.push(exec);
+        }
+
+        events
+    }
+
+    // expire GTD/DAY orders across every orderbook, each book's expiries are followed by the
+    // cancels of any order group legs they took with them
+    pub fn expire_orders(&mut self) -> Vec<Execution> {
+        let mut executions: Vec<Execution> = Vec::new();
+
+        let stock_ids: Vec<uuid::Uuid> = self.orderbooks.values().map(|o| o.stock_id).collect();
+        for stock_id in stock_ids {
+            let expired = match self.orderbooks.get_mut(&stock_id.to_string()) {
+                Some(orderbook) => orderbook.expire_orders(),
+                None => continue,
+            };
+            self._settle_order_groups(stock_id, &expired);
+            executions.extend(expired);
+            if let Some(orderbook) = self.orderbooks.get_mut(&stock_id.to_string()) {
+                executions.extend(orderbook.drain_events());
+            }
+        }
+
+        executions
+    }
+
+    // put a stock's orderbook into call-auction mode
+    pub fn start_auction(&mut self, stock_id: uuid::Uuid) -> Result<(), OrderError> {
+        match self.orderbooks.get_mut(&stock_id.to_string()) {
+            Some(orderbook) => {
+                orderbook.start_auction();
+                Ok(())
+            }
+            None => Err(OrderError::InvalidStockID),
         }
+    }
+
+    // uncross a stock's auction and return the resulting executions, anything they cause
+    // (stop triggers, order group legs) is left on the book's events
+    pub fn uncross(&mut self, stock_id: uuid::Uuid) -> Result<Vec<Execution>, OrderError> {
+        let executions = match self.orderbooks.get_mut(&stock_id.to_string()) {
+            Some(orderbook) => orderbook.uncross()?,
+            None => return Err(OrderError::InvalidStockID),
+        };
+        self._settle_order_groups(stock_id, &executions);
 
         Ok(executions)
     }
 
-    // modify order
+    // amend an order by id, an amend into a crossing price can fill order group legs
     pub fn modify_order(
         &mut self,
-        stock: Stock,
         order_id: uuid::Uuid,
         new_qty: i32,
-        new_price: Option<f32>,
-    ) -> Result<(), OrderError> {
-        // get stock id from oid map
-        let orderbook = match self.orderbooks.get_mut(&stock.stock_id.to_string()) {
+        new_price: Option<Price>,
+    ) -> Result<Execution, OrderError> {
+        let stock_id = self._order_stock(order_id)?;
+        let orderbook = match self.orderbooks.get_mut(&stock_id.to_string()) {
+            Some(orderbook) => orderbook,
+            None => return Err(OrderError::InvalidStockID),
+        };
+
+        let exec = orderbook.modify_order(order_id, new_qty, new_price)?;
+        self._settle_order_groups(stock_id, std::slice::from_ref(&exec));
+
+        Ok(exec)
+    }
+
+    // cancel an order and submit its replacement on the same stock in one step
+    pub fn cancel_replace(
+        &mut self,
+        order_id: uuid::Uuid,
+        new_order: Order,
+    ) -> Result<Execution, OrderError> {
+        // orders in a group are managed through the group
+        if self.group_orders.contains_key(&order_id) {
+            return Err(OrderError::InvalidOrderGroup);
+        }
+
+        let stock_id = new_order.stock.stock_id;
+        let orderbook = match self.orderbooks.get_mut(&stock_id.to_string()) {
             Some(orderbook) => orderbook,
             None => return Err(OrderError::InvalidStockID),
         };
 
-        // modify order
-        match orderbook.modify_order(order_id, new_qty, new_price) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+        let exec = match orderbook.cancel_replace(order_id, new_order.clone()) {
+            Ok(exec) => exec,
+            Err(e) => {
+                self._track_rejection(&new_order);
+                return Err(e);
+            }
+        };
+        self._settle_order_groups(stock_id, std::slice::from_ref(&exec));
+
+        Ok(exec)
+    }
+
+    // delete an order by id, the rest of its order group goes with it
+    pub fn delete_order(&mut self, order_id: uuid::Uuid) -> Result<Execution, OrderError> {
+        let stock_id = self._order_stock(order_id)?;
+        let mut exec = self._remove_order(stock_id, order_id, ExecutionType::DELETE)?;
+        if let Some(orderbook) = self.orderbooks.get_mut(&stock_id.to_string()) {
+            orderbook.stamp(&mut exec);
+            orderbook._reprice_pegged_orders();
+        }
+        self._settle_order_groups(stock_id, std::slice::from_ref(&exec));
+
+        Ok(exec)
+    }
+
+    // pull every live order matching the filter, one book at a time, returning a single
+    // MASS_CANCEL per book. Cancelling part of an order group takes the rest of the group
+    // with it, those cancels are left on the book's events
+    pub fn mass_cancel(&mut self, filter: CancelFilter) -> Vec<Execution> {
+        let mut executions: Vec<Execution> = Vec::new();
+        // cancels for more than one creator are put down to the exchange itself
+        let executor_id = filter.creator_id.unwrap_or_else(uuid::Uuid::nil);
+
+        let stock_ids: Vec<uuid::Uuid> = self
+            .orderbooks
+            .values()
+            .map(|orderbook| orderbook.stock_id)
+            .filter(|stock_id| filter.stock_id.is_none_or(|id| id == *stock_id))
+            .collect();
+        for stock_id in stock_ids {
+            let order_ids: Vec<uuid::Uuid> = match self.orderbooks.get(&stock_id.to_string()) {
+                Some(orderbook) => orderbook
+                    .oid_map
+                    .values()
+                    .chain(orderbook.trigger_book.orders.values())
+                    .filter(|order| filter.matches(order))
+                    .map(|order| order.order_id)
+                    .collect(),
+                None => continue,
+            };
+
+            let mut cancelled: Vec<Execution> = Vec::new();
+            for order_id in order_ids {
+                if let Ok(exec) = self._remove_order(stock_id, order_id, ExecutionType::CANCEL) {
+                    cancelled.push(exec);
+                }
+            }
+
+            // sequenced ahead of any group cancels it causes
+            let orders: Vec<Order> = cancelled.iter().map(|exec| exec.order.clone()).collect();
+            let mut exec = match Execution::mass_cancel(executor_id, self.clock.now(), orders) {
+                Some(exec) => exec,
+                None => continue,
+            };
+            // pegs left in the book only move once the whole batch is out
+            if let Some(orderbook) = self.orderbooks.get_mut(&stock_id.to_string()) {
+                orderbook.stamp(&mut exec);
+                orderbook._reprice_pegged_orders();
+            }
+            self._settle_order_groups(stock_id, &cancelled);
+            executions.push(exec);
+        }
+
+        executions
+    }
+
+    // register a client session, replacing any open session with the same id
+    pub fn open_session(&mut self, session: Session) {
+        self.sessions.insert(session.session_id, session);
+    }
+
+    // to be called when a client connection drops, pulls the session's orders if it asked for
+    // cancel on disconnect
+    pub fn disconnect(&mut self, session_id: uuid::Uuid) -> Vec<Execution> {
+        match self.sessions.remove(&session_id) {
+            Some(session) if session.cancel_on_disconnect => self.mass_cancel(session.filter()),
+            _ => Vec::new(),
+        }
+    }
+
+    // stock an order id belongs to, from the index of live orders
+    pub fn get_order_stock(&self, order_id: uuid::Uuid) -> Option<uuid::Uuid> {
+        self.order_stocks.get(&order_id).copied()
+    }
+
+    fn _order_stock(&self, order_id: uuid::Uuid) -> Result<uuid::Uuid, OrderError> {
+        match self.get_order_stock(order_id) {
+            Some(stock_id) => Ok(stock_id),
+            None => Err(OrderError::InvalidOrderID),
         }
     }
 
-    // delete order
-    pub fn delete_order(&mut self, stock: Stock, order_id: uuid::Uuid) -> Result<(), OrderError> {
-        let orderbook = match self.orderbooks.get_mut(&stock.stock_id.to_string()) {
+    // take a resting or untriggered order out of its book, reporting it (unsequenced) with
+    // exec_type. pegged orders are left for the caller to reprice
+    fn _remove_order(
+        &mut self,
+        stock_id: uuid::Uuid,
+        order_id: uuid::Uuid,
+        exec_type: ExecutionType,
+    ) -> Result<Execution, OrderError> {
+        let orderbook = match self.orderbooks.get_mut(&stock_id.to_string()) {
             Some(orderbook) => orderbook,
             None => return Err(OrderError::InvalidStockID),
         };
+        let order = match orderbook
+            .oid_map
+            .get(&order_id)
+            .or_else(|| orderbook.trigger_book.orders.get(&order_id))
+        {
+            Some(order) => order.clone(),
+            None => return Err(OrderError::InvalidOrderID),
+        };
+
+        orderbook._delete_order(order_id)?;
+
+        Ok(Execution::new(
+            exec_type,
+            order.creator_id,
+            self.clock.now(),
+            order,
+            Vec::new(),
+        ))
+    }
+
+    // use a different time source for the exchange and all of its orderbooks
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        for orderbook in self.orderbooks.values_mut() {
+            orderbook.set_clock(clock.clone());
+        }
+        self.clock = clock;
+    }
+
+    // how long (in seconds) finished orders can still be looked up
+    pub fn set_status_retention(&mut self, secs: u32) {
+        self.status_retention = secs;
+        self._prune_order_registry();
+    }
+
+    // latest state of an order, None if it was never seen or finished too long ago
+    pub fn get_order_status(&self, order_id: uuid::Uuid) -> Option<&OrderState> {
+        self.order_registry.get(&order_id)
+    }
+
+    // latest state of every order a creator has live or recently finished
+    pub fn get_creator_orders(&self, creator_id: uuid::Uuid) -> Vec<&OrderState> {
+        match self.creator_orders.get(&creator_id) {
+            Some(order_ids) => order_ids
+                .iter()
+                .filter_map(|order_id| self.order_registry.get(order_id))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // follow executions through to the state of every order they touch
+    fn _track_executions(&mut self, executions: &[Execution]) {
+        let now = self.clock.now();
+
+        for exec in executions {
+            // a replaced order is cancelled in favour of its replacement
+            if let Some(previous) = &exec.previous_order {
+                if previous.order_id != exec.order.order_id {
+                    self.order_stocks.remove(&previous.order_id);
+                    if let Some(state) = self.order_registry.get_mut(&previous.order_id) {
+                        state.status = OrderStatus::CANCELLED;
+                        state.time_updated = now;
+                        self.finished_orders.push_back((now, previous.order_id));
+                    }
+                }
+            }
+
+            // resting orders on the other side of the fills
+            for fill in exec.fills.iter() {
+                if fill.maker_order_id == exec.order.order_id {
+                    continue;
+                }
+                if let Some(state) = self.order_registry.get_mut(&fill.maker_order_id) {
+                    state.order.qty -= fill.qty;
+                    state._fill(fill.price, fill.qty);
+                    state.status = state._open_status();
+                    state.time_updated = now;
+                    if state.status.is_final() {
+                        self.order_stocks.remove(&fill.maker_order_id);
+                        self.finished_orders.push_back((now, fill.maker_order_id));
+                    }
+                }
+            }
+
+            // the order the execution is about
+            let order_id = exec.order.order_id;
+            let state = self
+                .order_registry
+                .entry(order_id)
+                .or_insert_with(|| OrderState::new(exec.order.clone(), now));
+            if state.status.is_final() {
+                self.order_stocks.remove(&order_id);
+                continue;
+            }
+            state.order = exec.order.clone();
+            for fill in exec.fills.iter().filter(|f| f.taker_order_id == order_id) {
+                state._fill(fill.price, fill.qty);
+            }
+            state.status = match exec.exec_type {
+                ExecutionType::CANCEL | ExecutionType::DELETE => OrderStatus::CANCELLED,
+                ExecutionType::EXPIRE => OrderStatus::EXPIRED,
+                _ => state._open_status(),
+            };
+            state.time_updated = now;
+            if state.status.is_final() {
+                self.order_stocks.remove(&order_id);
+                self.finished_orders.push_back((now, order_id));
+            } else {
+                self.order_stocks
+                    .insert(order_id, exec.order.stock.stock_id);
+            }
+            self.creator_orders
+                .entry(exec.order.creator_id)
+                .or_default()
+                .insert(order_id);
+        }
+
+        self._prune_order_registry();
+    }
+
+    // record an order the exchange turned away, unless it's already known under that id
+    fn _track_rejection(&mut self, order: &Order) {
+        if self.order_registry.contains_key(&order.order_id) {
+            return;
+        }
+        let now = self.clock.now();
+        let mut state = OrderState::new(order.clone(), now);
+        state.status = OrderStatus::REJECTED;
+        self.order_registry.insert(order.order_id, state);
+        self.creator_orders
+            .entry(order.creator_id)
+            .or_default()
+            .insert(order.order_id);
+        self.finished_orders.push_back((now, order.order_id));
+        self._prune_order_registry();
+    }
+
+    // forget finished orders once they're past the retention window
+    fn _prune_order_registry(&mut self) {
+        let now = self.clock.now();
+        while let Some(&(time_finished, order_id)) = self.finished_orders.front() {
+            if time_finished.saturating_add(self.status_retention) > now {
+                break;
+            }
+            self.finished_orders.pop_front();
 
-        // delete order
-        match orderbook.delete_order(order_id) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+            let creator_id = match self.order_registry.get(&order_id) {
+                Some(state) if state.status.is_final() && state.time_updated <= time_finished => {
+                    state.order.creator_id
+                }
+                _ => continue,
+            };
+            self.order_registry.remove(&order_id);
+            if let Some(order_ids) = self.creator_orders.get_mut(&creator_id) {
+                order_ids.remove(&order_id);
+                if order_ids.is_empty() {
+                    self.creator_orders.remove(&creator_id);
+                }
+            }
         }
     }
 
@@ -976,7 +3743,8 @@ impl Exchange {
         let mut bid_price_levels: Vec<PriceLevel> = Vec::new();
         let mut ask_price_levels: Vec<PriceLevel> = Vec::new();
 
-        for (_, price_level) in orderbook.bid_price_levels.iter() {
+        // best bid first
+        for (_, price_level) in orderbook.bid_price_levels.iter().rev() {
             bid_price_levels.push(price_level.clone());
         }
 
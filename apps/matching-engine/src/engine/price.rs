@@ -0,0 +1,204 @@
+use core::fmt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Sub};
+
+use crate::helpers::helpers;
+use serde::{Deserialize, Serialize};
+
+// fixed-point price: an integer number of ticks at a given decimal scale,
+// e.g. Price { ticks: 10005, scale: 2 } is 100.05
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Price {
+    pub ticks: i64,
+    pub scale: u32,
+}
+
+impl Price {
+    // create new price from raw ticks
+    pub fn new(ticks: i64, scale: u32) -> Self {
+        Price { ticks, scale }
+    }
+
+    // create new price from a float, rounding (not truncating) to the given scale
+    pub fn from_f64(value: f64, scale: u32) -> Self {
+        let ticks = (value * 10_f64.powi(scale as i32)).round() as i64;
+        Price { ticks, scale }
+    }
+
+    // lossy conversion back to a float, only meant for display/analytics
+    pub fn to_f64(&self) -> f64 {
+        self.ticks as f64 / 10_f64.powi(self.scale as i32)
+    }
+
+    // express the price at another scale, returns None if precision would be lost
+    pub fn rescale(&self, scale: u32) -> Option<Price> {
+        if scale >= self.scale {
+            let ticks = self
+                .ticks
+                .checked_mul(helpers::pow10(scale - self.scale)?)?;
+            Some(Price::new(ticks, scale))
+        } else {
+            let factor = match helpers::pow10(self.scale - scale) {
+                Some(factor) => factor,
+                // dividing by more than i64 can hold only leaves zero whole
+                None => return (self.ticks == 0).then_some(Price::new(0, scale)),
+            };
+            if self.ticks % factor != 0 {
+                return None;
+            }
+            Some(Price::new(self.ticks / factor, scale))
+        }
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.ticks > 0
+    }
+
+    // whether the price is a whole number of steps (e.g. a tick size)
+    pub fn is_multiple_of(&self, step: Price) -> bool {
+        match self.normalized(&step) {
+            Some((lhs, rhs)) => rhs != 0 && lhs % rhs == 0,
+            None => false,
+        }
+    }
+
+    // value of qty units at this price, None if it doesn't fit
+    pub fn notional(&self, qty: i32) -> Option<Price> {
+        Some(Price::new(self.ticks.checked_mul(qty as i64)?, self.scale))
+    }
+
+    // exact half of the price, adding a decimal place when the ticks are odd
+    pub fn half(&self) -> Option<Price> {
+        if self.ticks % 2 == 0 {
+            Some(Price::new(self.ticks / 2, self.scale))
+        } else {
+            Some(Price::new(
+                self.ticks.checked_mul(5)?,
+                self.scale.checked_add(1)?,
+            ))
+        }
+    }
+
+    // nearest multiple of step at or below the price
+    pub fn floor_to(&self, step: Price) -> Option<Price> {
+        let (lhs, rhs) = self.normalized(&step)?;
+        let ticks = lhs.checked_div_euclid(rhs)?.checked_mul(rhs)?;
+        Some(Price::new(
+            i64::try_from(ticks).ok()?,
+            self.scale.max(step.scale),
+        ))
+    }
+
+    // nearest multiple of step at or above the price
+    pub fn ceil_to(&self, step: Price) -> Option<Price> {
+        let (lhs, rhs) = self.normalized(&step)?;
+        let ticks = -(-lhs).checked_div_euclid(rhs)?.checked_mul(rhs)?;
+        Some(Price::new(
+            i64::try_from(ticks).ok()?,
+            self.scale.max(step.scale),
+        ))
+    }
+
+    // sum of two prices, None if it doesn't fit
+    pub fn checked_add(&self, other: Price) -> Option<Price> {
+        let (lhs, rhs) = self.normalized(&other)?;
+        let ticks = i64::try_from(lhs.checked_add(rhs)?).ok()?;
+        Some(Price::new(ticks, self.scale.max(other.scale)))
+    }
+
+    // difference of two prices, None if it doesn't fit
+    pub fn checked_sub(&self, other: Price) -> Option<Price> {
+        let (lhs, rhs) = self.normalized(&other)?;
+        let ticks = i64::try_from(lhs.checked_sub(rhs)?).ok()?;
+        Some(Price::new(ticks, self.scale.max(other.scale)))
+    }
+
+    // ticks of both prices at a common scale, None if either doesn't fit
+    fn normalized(&self, other: &Price) -> Option<(i128, i128)> {
+        let scale = self.scale.max(other.scale);
+        let lhs = Self::_scale_up(self.ticks, scale - self.scale)?;
+        let rhs = Self::_scale_up(other.ticks, scale - other.scale)?;
+        Some((lhs, rhs))
+    }
+
+    fn _scale_up(ticks: i64, exp: u32) -> Option<i128> {
+        if ticks == 0 {
+            return Some(0);
+        }
+        (ticks as i128).checked_mul(10_i128.checked_pow(exp)?)
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.normalized(other) {
+            Some((lhs, rhs)) => lhs.cmp(&rhs),
+            // only the price with the smaller scale is scaled up, and it can only outgrow i128
+            // by dwarfing the other, so its sign decides
+            None if self.scale < other.scale => self.ticks.cmp(&0),
+            None => 0.cmp(&other.ticks),
+        }
+    }
+}
+
+impl Hash for Price {
+    // equal prices at different scales must hash the same, so strip trailing zeros first
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut ticks = self.ticks;
+        let mut scale = self.scale;
+        while scale > 0 && ticks % 10 == 0 {
+            ticks /= 10;
+            scale -= 1;
+        }
+        ticks.hash(state);
+        scale.hash(state);
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        self.checked_add(other)
+            .expect("attempt to add prices with overflow")
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        self.checked_sub(other)
+            .expect("attempt to subtract prices with overflow")
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.ticks < 0 { "-" } else { "" };
+        let abs = self.ticks.unsigned_abs();
+        if self.scale == 0 {
+            return write!(f, "{}{}", sign, abs);
+        }
+        // pad with zeros so there's at least one digit before the point
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", abs, width = scale + 1);
+        let (whole, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, frac)
+    }
+}
@@ -10,6 +10,25 @@ pub enum OrderError {
     InvalidOrderSide,
     InvalidTimeCreated,
     OrderQueueEmpty,
+    PriceNotOnTick,
+    QuantityNotOnLot,
+    QuantityBelowMinimum,
+    QuantityAboveMaximum,
+    NotionalBelowMinimum,
+    NotionalAboveMaximum,
+    InsufficientLiquidity,
+    InvalidStopPrice,
+    InvalidDisplayQuantity,
+    PostOnlyWouldCross,
+    InvalidTimeInForce,
+    InvalidExpireTime,
+    OrderExpired,
+    InvalidTrailingOffset,
+    InvalidPeg,
+    PegReferenceUnavailable,
+    InvalidOrderGroup,
+    InvalidMinQuantity,
+    InvalidMarketProtection,
     Other(String), // Catch-all for unexpected errors, with a descriptive message.
 }
 
@@ -24,11 +43,102 @@ impl fmt::Display for OrderError {
             OrderError::InvalidOrderSide => write!(f, "InvalidOrderSide"),
             OrderError::InvalidTimeCreated => write!(f, "InvalidTimeCreated"),
             OrderError::OrderQueueEmpty => write!(f, "OrderQueueEmpty"),
+            OrderError::PriceNotOnTick => write!(f, "PriceNotOnTick"),
+            OrderError::QuantityNotOnLot => write!(f, "QuantityNotOnLot"),
+            OrderError::QuantityBelowMinimum => write!(f, "QuantityBelowMinimum"),
+            OrderError::QuantityAboveMaximum => write!(f, "QuantityAboveMaximum"),
+            OrderError::NotionalBelowMinimum => write!(f, "NotionalBelowMinimum"),
+            OrderError::NotionalAboveMaximum => write!(f, "NotionalAboveMaximum"),
+            OrderError::InsufficientLiquidity => write!(f, "InsufficientLiquidity"),
+            OrderError::InvalidStopPrice => write!(f, "InvalidStopPrice"),
+            OrderError::InvalidDisplayQuantity => write!(f, "InvalidDisplayQuantity"),
+            OrderError::PostOnlyWouldCross => write!(f, "PostOnlyWouldCross"),
+            OrderError::InvalidTimeInForce => write!(f, "InvalidTimeInForce"),
+            OrderError::InvalidExpireTime => write!(f, "InvalidExpireTime"),
+            OrderError::OrderExpired => write!(f, "OrderExpired"),
+            OrderError::InvalidTrailingOffset => write!(f, "InvalidTrailingOffset"),
+            OrderError::InvalidPeg => write!(f, "InvalidPeg"),
+            OrderError::PegReferenceUnavailable => write!(f, "PegReferenceUnavailable"),
+            OrderError::InvalidOrderGroup => write!(f, "InvalidOrderGroup"),
+            OrderError::InvalidMinQuantity => write!(f, "InvalidMinQuantity"),
+            OrderError::InvalidMarketProtection => write!(f, "InvalidMarketProtection"),
             OrderError::Other(e) => write!(f, "Other: {}", e),
         }
     }
 }
 
+impl OrderError {
+    // stable numeric reason code sent to clients with rejections, existing codes must never
+    // be reused or renumbered
+    pub fn code(&self) -> u32 {
+        match self {
+            OrderError::InvalidOrderID => 1,
+            OrderError::InvalidCreatorID => 2,
+            OrderError::InvalidStockID => 3,
+            OrderError::InvalidPrice => 4,
+            OrderError::InvalidQuantity => 5,
+            OrderError::InvalidOrderSide => 6,
+            OrderError::InvalidTimeCreated => 7,
+            OrderError::OrderQueueEmpty => 8,
+            OrderError::PriceNotOnTick => 9,
+            OrderError::QuantityNotOnLot => 10,
+            OrderError::QuantityBelowMinimum => 11,
+            OrderError::QuantityAboveMaximum => 12,
+            OrderError::NotionalBelowMinimum => 13,
+            OrderError::NotionalAboveMaximum => 14,
+            OrderError::InsufficientLiquidity => 15,
+            OrderError::InvalidStopPrice => 16,
+            OrderError::InvalidDisplayQuantity => 17,
+            OrderError::PostOnlyWouldCross => 18,
+            OrderError::InvalidTimeInForce => 19,
+            OrderError::InvalidExpireTime => 20,
+            OrderError::OrderExpired => 21,
+            OrderError::InvalidTrailingOffset => 22,
+            OrderError::InvalidPeg => 23,
+            OrderError::PegReferenceUnavailable => 24,
+            OrderError::InvalidOrderGroup => 25,
+            OrderError::InvalidMinQuantity => 26,
+            OrderError::InvalidMarketProtection => 27,
+            OrderError::Other(_) => 999,
+        }
+    }
+
+    // human readable explanation of the rejection
+    pub fn reason(&self) -> String {
+        let reason = match self {
+            OrderError::InvalidOrderID => "unknown or invalid order id",
+            OrderError::InvalidCreatorID => "invalid creator id",
+            OrderError::InvalidStockID => "unknown or invalid stock",
+            OrderError::InvalidPrice => "invalid price",
+            OrderError::InvalidQuantity => "invalid quantity",
+            OrderError::InvalidOrderSide => "invalid order side",
+            OrderError::InvalidTimeCreated => "invalid creation time",
+            OrderError::OrderQueueEmpty => "no orders queued",
+            OrderError::PriceNotOnTick => "price is not a multiple of the tick size",
+            OrderError::QuantityNotOnLot => "quantity is not a multiple of the lot size",
+            OrderError::QuantityBelowMinimum => "quantity is below the minimum",
+            OrderError::QuantityAboveMaximum => "quantity is above the maximum",
+            OrderError::NotionalBelowMinimum => "notional is below the minimum",
+            OrderError::NotionalAboveMaximum => "notional is above the maximum",
+            OrderError::InsufficientLiquidity => "not enough liquidity to fill in full",
+            OrderError::InvalidStopPrice => "invalid stop price",
+            OrderError::InvalidDisplayQuantity => "invalid display quantity",
+            OrderError::PostOnlyWouldCross => "post-only order would take liquidity",
+            OrderError::InvalidTimeInForce => "time in force not allowed for this order",
+            OrderError::InvalidExpireTime => "invalid expire time",
+            OrderError::OrderExpired => "order has expired",
+            OrderError::InvalidTrailingOffset => "invalid trailing offset",
+            OrderError::InvalidPeg => "invalid peg",
+            OrderError::PegReferenceUnavailable => "no price to peg to",
+            OrderError::InvalidOrderGroup => "invalid order group",
+            OrderError::InvalidMinQuantity => "invalid minimum quantity",
+            OrderError::InvalidMarketProtection => "invalid market protection",
+            OrderError::Other(e) => return e.clone(),
+        };
+        String::from(reason)
+    }
+}
+
 #[derive(Debug)]
 pub enum StockError {
     InvalidStockID,
@@ -38,6 +148,11 @@ pub enum StockError {
     InvalidTotalIssued,
     InvalidOutstandingShares,
     DuplicateStockID,
+    InvalidTickSize,
+    InvalidLotSize,
+    InvalidOrderLimits,
+    InvalidAllocation,
+    InvalidPriceScale,
     Other(String), // Catch-all for unexpected errors, with a descriptive message.
 }
 
@@ -51,6 +166,11 @@ impl fmt::Display for StockError {
             StockError::InvalidTotalIssued => write!(f, "InvalidTotalIssued"),
             StockError::InvalidOutstandingShares => write!(f, "InvalidOutstandingShares"),
             StockError::DuplicateStockID => write!(f, "DuplicateStockID"),
+            StockError::InvalidTickSize => write!(f, "InvalidTickSize"),
+            StockError::InvalidLotSize => write!(f, "InvalidLotSize"),
+            StockError::InvalidOrderLimits => write!(f, "InvalidOrderLimits"),
+            StockError::InvalidAllocation => write!(f, "InvalidAllocation"),
+            StockError::InvalidPriceScale => write!(f, "InvalidPriceScale"),
             StockError::Other(e) => write!(f, "Other: {}", e),
         }
     }
@@ -1 +1,2 @@
-pub mod helpers;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod helpers;
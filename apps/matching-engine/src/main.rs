@@ -1,10 +1,8 @@
-mod engine;
-mod errors;
-mod helpers;
 use engine::orderbook::Order;
 use engine::orderbook::Stock;
-use uuid::Uuid;
 use rand::Rng;
+use smolexchange::engine;
+use uuid::Uuid;
 
 fn main() {
     // create new stock
@@ -18,10 +16,10 @@ fn main() {
     );
     let mut o_book = engine::orderbook::OrderBook::new(stock.clone());
     let mut orders: Vec<Order> = Vec::new();
-    for i in 0..100 {
-        let price: f32 = rand::thread_rng().gen_range(90.0..100.0);
-        // Round to 2 decimal places
-        let rounded_price = (price * 100.0).round() / 100.0;
+    for _ in 0..100 {
+        let price: f64 = rand::thread_rng().gen_range(90.0..100.0);
+        // Round to the stock's price scale
+        let rounded_price = stock.price(price);
         let order = Order::new(
             Uuid::new_v4(),
             Uuid::new_v4(),
@@ -38,7 +36,10 @@ fn main() {
             chrono::Utc::now().timestamp() as u32,
             Some(rounded_price),
         );
-        orders.push(order);
+        match order {
+            Ok(order) => orders.push(order),
+            Err(e) => println!("Error creating order: {:?}", e),
+        }
     }
 
     for order in &orders {
@@ -63,7 +64,7 @@ fn main() {
             Ok(_) => {
                 // assert if it's in the orderbook
                 print!("checking if order is in orderbook: {} ... ", o.order_id);
-                assert_eq!(o_book.get_oid_map().contains_key(&o.order_id), false);
+                assert!(!o_book.get_oid_map().contains_key(&o.order_id));
                 println!("Order matched successfully!");
                 matched_orders.push(o.clone());
             }
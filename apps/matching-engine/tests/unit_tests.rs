@@ -7,22 +7,26 @@ mod tests {
     use rand::prelude::*;
     use rand_chacha::ChaCha8Rng;
     use rand_distr::{Distribution, Triangular, TriangularError};
-    use redis::PubSubCommands;
-    use smolexchange::engine::engine::MatchingEngine;
+    use smolexchange::engine::allocation::{Allocation, MatchingPolicy};
+    use smolexchange::engine::clock::ManualClock;
+    use smolexchange::engine::engine::{MatchingEngine, REJECTS_CHANNEL};
     use smolexchange::engine::orderbook::*;
+    use smolexchange::engine::price::Price;
     use smolexchange::engine::*;
-    use tokio;
+    use smolexchange::errors::OrderError;
+    use std::sync::Arc;
     use uuid::Uuid;
 
     const SEED: u64 = 69420;
+    #[allow(dead_code)]
     fn generate_orders(stock: Stock) -> Vec<Order> {
         let mut rng = ChaCha8Rng::seed_from_u64(SEED);
         let mut orders: Vec<Order> = Vec::new();
         // Bid Orders
-        for i in 0..100 {
+        for _ in 0..100 {
             let price: f32 = rng.gen_range(87.00..90.00);
             // Round to 2 decimal places
-            let rounded_price = (price * 100.0).round() / 100.0;
+            let rounded_price = stock.price(price as f64);
             let order = Order::new(
                 Uuid::new_v4(),
                 Uuid::new_v4(),
@@ -32,18 +36,19 @@ mod tests {
                     0 => orderbook::OrderType::LIMIT,
                     _ => orderbook::OrderType::MARKET,
                 },
-                rng.gen_range(10..100) as i32,
+                rng.gen_range(10..100),
                 chrono::Utc::now().timestamp() as u32,
                 Some(rounded_price),
-            );
+            )
+            .unwrap();
             orders.push(order);
         }
 
         // Ask Orders
-        for i in 0..100 {
+        for _ in 0..100 {
             let price: f32 = rng.gen_range(89.00..92.00);
             // Round to 2 decimal places
-            let rounded_price = (price * 100.0).round() / 100.0;
+            let rounded_price = stock.price(price as f64);
             let order = Order::new(
                 Uuid::new_v4(),
                 Uuid::new_v4(),
@@ -53,10 +58,11 @@ mod tests {
                     0 => orderbook::OrderType::LIMIT,
                     _ => orderbook::OrderType::MARKET,
                 },
-                rng.gen_range(10..100) as i32,
+                rng.gen_range(10..100),
                 chrono::Utc::now().timestamp() as u32,
                 Some(rounded_price),
-            );
+            )
+            .unwrap();
             orders.push(order);
         }
         orders
@@ -64,6 +70,7 @@ mod tests {
 
     // a function for generating orders in a triangular distribution using rand_distr,
     // lets the user specify what type of orders to generate, the amount, price range, etc.
+    #[allow(dead_code)]
     fn generate_triangular_orders(
         stock: Stock,
         num_orders: usize,
@@ -75,24 +82,18 @@ mod tests {
         let mut rng = ChaCha8Rng::seed_from_u64(SEED);
         let mut orders: Vec<Order> = Vec::new();
         let triangular: Triangular<f32> =
-            match Triangular::new(price_range.0, price_range.1, price_range.1) {
-                Ok(t) => t,
-                Err(e) => return Err(e),
-            };
+            Triangular::new(price_range.0, price_range.1, price_range.1)?;
 
-        let qty_sample = match Triangular::new(
+        let qty_sample = Triangular::new(
             quantity_range.0 as f32,
             quantity_range.1 as f32,
             quantity_range.0 as f32,
-        ) {
-            Ok(t) => t,
-            Err(e) => return Err(e),
-        };
+        )?;
 
-        for i in 0..num_orders {
+        for _ in 0..num_orders {
             let price: f32 = triangular.sample(&mut rng);
             // Round to 2 decimal places
-            let rounded_price = (price * 100.0).round() / 100.0;
+            let rounded_price = stock.price(price as f64);
             let order = Order::new(
                 Uuid::new_v4(),
                 Uuid::new_v4(),
@@ -102,7 +103,8 @@ mod tests {
                 qty_sample.sample(&mut rng) as i32,
                 chrono::Utc::now().timestamp() as u32,
                 Some(rounded_price),
-            );
+            )
+            .unwrap();
             orders.push(order);
         }
         Ok(orders)
@@ -114,8 +116,8 @@ mod tests {
         order_side: orderbook::OrderSide,
         order_type: orderbook::OrderType,
         order_qty: i32,
-        starting_price: Option<f32>,
-        order_inc: Option<f32>,
+        starting_price: Option<f64>,
+        order_inc: Option<f64>,
     ) -> Vec<Order> {
         let mut orders = Vec::new();
 
@@ -128,17 +130,59 @@ mod tests {
                 order_type,
                 order_qty,
                 chrono::Utc::now().timestamp() as u32,
-                match starting_price {
-                    Some(p) => Some(p + (i as f32 * order_inc.unwrap())),
-                    None => None,
-                },
-            );
+                starting_price.map(|p| stock.price(p + (i as f64 * order_inc.unwrap()))),
+            )
+            .unwrap();
             orders.push(order);
         }
 
         orders
     }
 
+    fn gen_stop_order(
+        stock: Stock,
+        order_side: orderbook::OrderSide,
+        order_type: orderbook::OrderType,
+        order_qty: i32,
+        price: Option<f64>,
+        stop_price: Option<f64>,
+        trailing_offset: Option<TrailingOffset>,
+    ) -> Order {
+        Order::new_stop(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            stock.clone(),
+            order_side,
+            order_type,
+            order_qty,
+            chrono::Utc::now().timestamp() as u32,
+            price.map(|p| stock.price(p)),
+            stop_price.map(|p| stock.price(p)),
+            trailing_offset,
+        )
+        .unwrap()
+    }
+
+    fn gen_pegged_order(
+        stock: Stock,
+        order_side: orderbook::OrderSide,
+        order_qty: i32,
+        peg: PegReference,
+        peg_offset: Option<f64>,
+    ) -> Order {
+        Order::new_pegged(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            stock.clone(),
+            order_side,
+            order_qty,
+            chrono::Utc::now().timestamp() as u32,
+            peg,
+            peg_offset.map(|p| stock.price(p)),
+        )
+        .unwrap()
+    }
+
     #[test]
     fn test_adding_orders_pricelevel() {
         // create new stock
@@ -150,9 +194,9 @@ mod tests {
             Some(1e6 as i32),
             Some(chrono::Utc::now().timestamp() as u32),
         );
-        let mut p_level = PriceLevel::new(1.0, 0);
+        let mut p_level = PriceLevel::new(stock.price(1.0), 0);
         let orders = gen_orders(
-            stock,
+            stock.clone(),
             10,
             orderbook::OrderSide::BID,
             orderbook::OrderType::LIMIT,
@@ -179,10 +223,10 @@ mod tests {
             Some(1e6 as i32),
             Some(chrono::Utc::now().timestamp() as u32),
         );
-        let mut p_level = PriceLevel::new(1.0, 0);
+        let mut p_level = PriceLevel::new(stock.price(1.0), 0);
 
         let orders = gen_orders(
-            stock,
+            stock.clone(),
             10,
             OrderSide::BID,
             OrderType::LIMIT,
@@ -231,7 +275,7 @@ mod tests {
         );
 
         for order in orders {
-            o_book.add_order(order);
+            o_book.add_order(order).unwrap();
         }
 
         // test number of orders in the orderbook
@@ -262,7 +306,7 @@ mod tests {
         );
 
         for order in orders {
-            o_book.queue_order(order);
+            o_book.queue_order(order).unwrap();
         }
 
         // test number of orders in the orderbook
@@ -293,7 +337,7 @@ mod tests {
         );
 
         for order in orders {
-            o_book.add_order(order);
+            o_book.add_order(order).unwrap();
         }
 
         // test number of orders in the orderbook
@@ -334,14 +378,13 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 200);
 
         // test get price level
-        let price_level = match o_book.get_price_level(OrderSide::BID, 1.0) {
-            Some(p) => Some(p.clone()),
-            None => None,
-        };
+        let price_level = o_book
+            .get_price_level(OrderSide::BID, stock.price(1.0))
+            .cloned();
 
         assert_eq!(price_level.clone().unwrap().orders.len(), 200);
         // test price
-        assert_eq!(price_level.clone().unwrap().price, 1.0);
+        assert_eq!(price_level.clone().unwrap().price, stock.price(1.0));
         // test quantity
         assert_eq!(price_level.clone().unwrap().qty, 20000);
     }
@@ -375,7 +418,7 @@ mod tests {
         );
 
         for ask in limit_asks {
-            o_book.queue_order(ask);
+            o_book.queue_order(ask).unwrap();
         }
 
         match o_book.execute_all_orders() {
@@ -396,7 +439,7 @@ mod tests {
         );
 
         for bid in limit_bids {
-            o_book.queue_order(bid);
+            o_book.queue_order(bid).unwrap();
         }
 
         // check order map and price levels
@@ -430,7 +473,7 @@ mod tests {
         );
 
         for bid in limit_bids1 {
-            o_book.queue_order(bid);
+            o_book.queue_order(bid).unwrap();
         }
 
         // execute orders
@@ -447,7 +490,7 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 7);
         assert_eq!(o_book.bid_price_levels.len(), 7);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(91.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(91.0)));
 
         let limit_asks1 = gen_orders(
             stock.clone(),
@@ -460,7 +503,7 @@ mod tests {
         );
 
         for ask in limit_asks1 {
-            o_book.queue_order(ask);
+            o_book.queue_order(ask).unwrap();
         }
 
         // execute orders
@@ -477,7 +520,7 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 0);
         assert_eq!(o_book.bid_price_levels.len(), 0);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(88.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(88.0)));
 
         // 3. limit asks in order book -> add market bid -> match
         println!("3. limit asks in order book -> add market bid -> match");
@@ -493,7 +536,7 @@ mod tests {
         );
 
         for ask in limit_asks2 {
-            o_book.queue_order(ask);
+            o_book.queue_order(ask).unwrap();
         }
 
         // execute orders
@@ -510,7 +553,7 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 7);
         assert_eq!(o_book.bid_price_levels.len(), 0);
         assert_eq!(o_book.ask_price_levels.len(), 7);
-        assert_eq!(o_book.last_market_price, Some(88.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(88.0)));
 
         let market_bids1 = gen_orders(
             stock.clone(),
@@ -523,7 +566,7 @@ mod tests {
         );
 
         for bid in market_bids1 {
-            o_book.queue_order(bid);
+            o_book.queue_order(bid).unwrap();
         }
 
         // execute orders
@@ -540,7 +583,7 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 0);
         assert_eq!(o_book.bid_price_levels.len(), 0);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(91.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(91.0)));
 
         // 4. limit bids in order book -> add market ask -> match
         println!("4. limit bids in order book -> add market ask -> match");
@@ -556,7 +599,7 @@ mod tests {
         );
 
         for bid in limit_bids2 {
-            o_book.queue_order(bid);
+            o_book.queue_order(bid).unwrap();
         }
 
         // execute orders
@@ -573,7 +616,7 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 7);
         assert_eq!(o_book.bid_price_levels.len(), 7);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(91.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(91.0)));
 
         let market_asks1 = gen_orders(
             stock.clone(),
@@ -586,7 +629,7 @@ mod tests {
         );
 
         for ask in market_asks1 {
-            o_book.queue_order(ask);
+            o_book.queue_order(ask).unwrap();
         }
 
         // execute orders
@@ -603,12 +646,12 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 0);
         assert_eq!(o_book.bid_price_levels.len(), 0);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(88.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(88.0)));
 
-        // 5. market asks in order book -> add limit bid -> match
-        println!("5. market asks in order book -> add limit bid -> match");
+        // 5. market asks into an empty book -> cancelled
+        println!("5. market asks into an empty book -> cancelled");
 
-        o_book.last_market_price = Some(69.0);
+        o_book.last_market_price = Some(stock.price(69.0));
 
         let market_asks2 = gen_orders(
             stock.clone(),
@@ -621,44 +664,14 @@ mod tests {
         );
 
         for ask in market_asks2 {
-            o_book.queue_order(ask);
-        }
-
-        // execute orders
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
-
-        // print orderbook
-        o_book.print_orderbook();
-
-        // check order queue, order map and price_levels
-        assert_eq!(o_book.order_queue.len(), 0);
-        assert_eq!(o_book.oid_map.len(), 7);
-        assert_eq!(o_book.bid_price_levels.len(), 0);
-        assert_eq!(o_book.ask_price_levels.len(), 1);
-        assert_eq!(o_book.last_market_price, Some(69.0));
-
-        let limit_bids3 = gen_orders(
-            stock.clone(),
-            7,
-            orderbook::OrderSide::BID,
-            orderbook::OrderType::LIMIT,
-            100,
-            Some(88.0),
-            Some(0.5),
-        );
-
-        for bid in limit_bids3 {
-            o_book.queue_order(bid);
+            o_book.queue_order(ask).unwrap();
         }
 
-        // execute orders
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
+        // execute orders, market orders never rest in the book
+        let execs = o_book.execute_all_orders().unwrap();
+        assert!(execs
+            .iter()
+            .all(|exec| exec.exec_type == ExecutionType::CANCEL && exec.fills.is_empty()));
 
         // print orderbook
         o_book.print_orderbook();
@@ -668,42 +681,10 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 0);
         assert_eq!(o_book.bid_price_levels.len(), 0);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(69.0));
-
-        // 6. market bids in order book -> add limit ask -> match
-        println!("6. market bids in order book -> add limit ask -> match");
+        assert_eq!(o_book.last_market_price, Some(stock.price(69.0)));
 
-        o_book.last_market_price = Some(69.0);
-
-        let market_bids1 = gen_orders(
-            stock.clone(),
-            7,
-            orderbook::OrderSide::BID,
-            orderbook::OrderType::MARKET,
-            100,
-            None,
-            None,
-        );
-
-        for bid in market_bids1.clone() {
-            o_book.queue_order(bid);
-        }
-
-        // execute orders
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
-
-        // print orderbook
-        o_book.print_orderbook();
-
-        // check order queue, order map and price_levels
-        assert_eq!(o_book.order_queue.len(), 0);
-        assert_eq!(o_book.oid_map.len(), 7);
-        assert_eq!(o_book.bid_price_levels.len(), 1);
-        assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(69.0));
+        // 6. limit asks in order book -> add market bid larger than the book -> match, cancel rest
+        println!("6. limit asks in order book -> add market bid larger than the book -> match");
 
         let limit_asks2 = gen_orders(
             stock.clone(),
@@ -716,171 +697,46 @@ mod tests {
         );
 
         for ask in limit_asks2.clone() {
-            o_book.queue_order(ask);
-        }
-
-        // execute orders
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
-
-        // print orderbook
-        o_book.print_orderbook();
-
-        // check order queue, order map and price_levels
-        assert_eq!(o_book.order_queue.len(), 0);
-        assert_eq!(o_book.oid_map.len(), 12);
-        assert_eq!(o_book.bid_price_levels.len(), 1);
-        assert_eq!(o_book.ask_price_levels.len(), 6);
-        assert_eq!(o_book.last_market_price, Some(69.0));
-
-        for order in limit_asks2.clone() {
-            match o_book.delete_order(order.order_id) {
-                Ok(_) => (),
-                Err(e) => println!("Error deleting order: {}", e),
-            }
-        }
-
-        for order in market_bids1.clone() {
-            match o_book.delete_order(order.order_id) {
-                Ok(_) => (),
-                Err(e) => println!("Error deleting order: {}", e),
-            }
-        }
-
-        // 7. market asks in order book -> add market bid -> match
-        println!("7. market asks in order book -> add market bid -> match");
-
-        o_book.last_market_price = Some(420.0);
-
-        let market_asks3 = gen_orders(
-            stock.clone(),
-            7,
-            orderbook::OrderSide::ASK,
-            orderbook::OrderType::MARKET,
-            100,
-            None,
-            None,
-        );
-
-        for ask in market_asks3 {
-            o_book.queue_order(ask);
-        }
-
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
+            o_book.queue_order(ask).unwrap();
         }
 
-        // print orderbook
-        o_book.print_orderbook();
-
-        // check order queue, order map and price_levels
-        assert_eq!(o_book.order_queue.len(), 0);
-        assert_eq!(o_book.oid_map.len(), 7);
-        assert_eq!(o_book.bid_price_levels.len(), 0);
-        assert_eq!(o_book.ask_price_levels.len(), 1);
-        assert_eq!(o_book.last_market_price, Some(420.0));
-
-        let market_bids2 = gen_orders(
+        let mut market_bid = gen_orders(
             stock.clone(),
-            7,
+            1,
             orderbook::OrderSide::BID,
             orderbook::OrderType::MARKET,
             100,
             None,
             None,
-        );
-
-        for bid in market_bids2 {
-            o_book.queue_order(bid);
-        }
+        )[0]
+        .clone();
+        market_bid.qty = 1000;
+        o_book.queue_order(market_bid).unwrap();
 
         // execute orders
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
-
-        // print orderbook
-        o_book.print_orderbook();
-
-        // check order queue, order map and price_levels
-        assert_eq!(o_book.order_queue.len(), 0);
-        assert_eq!(o_book.oid_map.len(), 0);
-        assert_eq!(o_book.bid_price_levels.len(), 0);
-        assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(420.0));
-
-        // 8. market bids in order book -> add market ask -> match
-        println!("8. market bids in order book -> add market ask -> match");
-
-        o_book.last_market_price = Some(21.0);
-
-        let market_bids3 = gen_orders(
-            stock.clone(),
-            7,
-            orderbook::OrderSide::BID,
-            orderbook::OrderType::MARKET,
-            100,
-            None,
-            None,
-        );
-
-        for bid in market_bids3 {
-            o_book.queue_order(bid);
-        }
-
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
+        let execs = o_book.execute_all_orders().unwrap();
 
         // print orderbook
         o_book.print_orderbook();
 
-        // check order queue, order map and price_levels
-        assert_eq!(o_book.order_queue.len(), 0);
-        assert_eq!(o_book.oid_map.len(), 7);
-        assert_eq!(o_book.bid_price_levels.len(), 1);
-        assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(21.0));
-
-        let market_asks4 = gen_orders(
-            stock.clone(),
-            7,
-            orderbook::OrderSide::ASK,
-            orderbook::OrderType::MARKET,
-            100,
-            None,
-            None,
-        );
-
-        for ask in market_asks4 {
-            o_book.queue_order(ask);
-        }
-
-        // execute orders
-        match o_book.execute_all_orders() {
-            Ok(_) => (),
-            Err(e) => println!("Error executing orders: {}", e),
-        }
-
-        // print orderbook
-        o_book.print_orderbook();
+        // the market bid walks all 7 levels, and the 300 left over is cancelled
+        let exec = execs.last().unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert_eq!(exec.fills.len(), 7);
+        assert_eq!(exec.filled_qty(), 700);
+        assert_eq!(exec.order.qty, 300);
 
         // check order queue, order map and price_levels
         assert_eq!(o_book.order_queue.len(), 0);
         assert_eq!(o_book.oid_map.len(), 0);
         assert_eq!(o_book.bid_price_levels.len(), 0);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(21.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(72.0)));
 
-        // 9. ??? random order types -> match
-        println!("9. ??? random order types -> match");
+        // 7. ??? random order types -> match
+        println!("7. ??? random order types -> match");
 
-        o_book.last_market_price = Some(60.0);
+        o_book.last_market_price = Some(stock.price(60.0));
 
         // generate orders from price range 60.0 to 65.0 in ranges in 0.5 increments.
         // 100 orders total, and ensure the quantity at each price level decreases
@@ -889,10 +745,10 @@ mod tests {
         let mut bids: Vec<Order> = Vec::new();
 
         for j in 0..10 {
-            for i in 0..20 {
-                let price: f32 = 65.0;
+            for _ in 0..20 {
+                let price: f64 = 65.0;
                 // Round to 2 decimal places
-                let rounded_price = (price * 100.0).round() / 100.0;
+                let rounded_price = stock.price(price - (j as f64 * 0.5));
                 let order = Order::new(
                     Uuid::new_v4(),
                     Uuid::new_v4(),
@@ -906,16 +762,17 @@ mod tests {
                     ),
                     orderbook::OrderSide::BID,
                     orderbook::OrderType::LIMIT,
-                    (10 - j) as i32,
+                    10 - j,
                     chrono::Utc::now().timestamp() as u32,
-                    Some(rounded_price - (j as f32 * 0.5)),
-                );
+                    Some(rounded_price),
+                )
+                .unwrap();
                 bids.push(order);
             }
         }
 
         for order in bids {
-            o_book.queue_order(order);
+            o_book.queue_order(order).unwrap();
         }
 
         // execute orders
@@ -932,15 +789,15 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 200);
         assert_eq!(o_book.bid_price_levels.len(), 10);
         assert_eq!(o_book.ask_price_levels.len(), 0);
-        assert_eq!(o_book.last_market_price, Some(60.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(60.0)));
 
         let mut asks = Vec::new();
 
         for j in 0..10 {
             for _ in 0..20 {
-                let price: f32 = 66.0;
+                let price: f64 = 66.0;
                 // Round to 2 decimal places
-                let rounded_price = (price * 100.0).round() / 100.0;
+                let rounded_price = stock.price(price + (j as f64 * 0.5));
                 let order = Order::new(
                     Uuid::new_v4(),
                     Uuid::new_v4(),
@@ -954,16 +811,17 @@ mod tests {
                     ),
                     orderbook::OrderSide::ASK,
                     orderbook::OrderType::LIMIT,
-                    (10 - j) as i32,
+                    10 - j,
                     chrono::Utc::now().timestamp() as u32,
-                    Some(rounded_price + (j as f32 * 0.5)),
-                );
+                    Some(rounded_price),
+                )
+                .unwrap();
                 asks.push(order);
             }
         }
 
         for order in asks {
-            o_book.queue_order(order);
+            o_book.queue_order(order).unwrap();
         }
 
         // execute orders
@@ -980,15 +838,16 @@ mod tests {
         let order0 = Order::new(
             Uuid::new_v4(),
             Uuid::new_v4(),
-            stock,
+            stock.clone(),
             orderbook::OrderSide::BID,
             orderbook::OrderType::LIMIT,
             10,
             chrono::Utc::now().timestamp() as u32,
-            Some(66.0),
-        );
+            Some(stock.price(66.0)),
+        )
+        .unwrap();
         // queue the order
-        o_book.queue_order(order0.clone());
+        o_book.queue_order(order0.clone()).unwrap();
         // execute the order
         match o_book.execute_all_orders() {
             Ok(_) => (),
@@ -1002,21 +861,56 @@ mod tests {
         assert_eq!(o_book.oid_map.len(), 399);
         assert_eq!(o_book.bid_price_levels.len(), 10);
         // check that the top bid price level qty is 190
-        let p_level = match o_book.get_price_level(OrderSide::ASK, 66.0) {
+        let p_level = match o_book.get_price_level(OrderSide::ASK, stock.price(66.0)) {
             Some(p) => p.clone(),
             None => panic!("Error getting price level"),
         };
         assert_eq!(p_level.qty, 190);
         assert_eq!(o_book.ask_price_levels.len(), 10);
-        assert_eq!(o_book.last_market_price, Some(66.0));
+        assert_eq!(o_book.last_market_price, Some(stock.price(66.0)));
     }
 
-    // test adding a stock to the exchange
+    // test fixed-point price conversions, formatting and ordering
     #[test]
-    fn test_exchange_add_stock() {
-        // create new exchange
-        let mut exchange = Exchange::new();
+    fn test_price_fixed_point() {
+        // rounding instead of truncating
+        assert_eq!(Price::from_f64(88.995, 2).ticks, 8900);
+        assert_eq!(Price::from_f64(0.1 + 0.2, 2).ticks, 30);
+        // large prices keep their precision
+        let big = Price::from_f64(123456.78, 2);
+        assert_eq!(big.ticks, 12345678);
+        assert_eq!(big.to_string(), "123456.78");
+        assert_eq!(Price::new(5, 2).to_string(), "0.05");
+        assert_eq!(Price::new(-5, 2).to_string(), "-0.05");
+        assert_eq!(Price::new(42, 0).to_string(), "42");
+
+        // equality and ordering are numeric, even across scales
+        assert_eq!(Price::new(10000, 2), Price::new(100000, 3));
+        assert!(Price::new(9950, 2) < Price::new(10000, 2));
+        assert_eq!(Price::new(10050, 2).rescale(1), Some(Price::new(1005, 1)));
+        assert_eq!(Price::new(10055, 2).rescale(1), None);
+
+        // far apart scales compare without overflowing, and arithmetic that doesn't fit is None
+        assert!(Price::new(1, 0) > Price::new(i64::MAX, 30));
+        assert!(Price::new(-1, 0) < Price::new(i64::MIN, 30));
+        assert_ne!(Price::new(1, 0), Price::new(1, 60));
+        assert!(!Price::new(1, 30).is_multiple_of(Price::new(1, 2)));
+        assert_eq!(Price::new(i64::MAX, 2).checked_add(Price::new(1, 2)), None);
+        assert_eq!(Price::new(i64::MIN, 2).checked_sub(Price::new(1, 2)), None);
+        assert_eq!(Price::new(i64::MAX, 2).half(), None);
+        assert_eq!(Price::new(1, 2).rescale(40), None);
+        assert_eq!(Price::new(5, 20).to_string(), "0.00000000000000000005");
+
+        // serialized payloads carry exact ticks
+        let json = serde_json::to_string(&Price::new(10005, 2)).unwrap();
+        let decoded: Price = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.ticks, 10005);
+        assert_eq!(decoded.scale, 2);
+    }
 
+    // test that price levels are ordered numerically and the best price is swept first
+    #[test]
+    fn test_best_bid_ask_ordering() {
         // create new stock
         let stock = Stock::new(
             Uuid::new_v4(),
@@ -1026,35 +920,83 @@ mod tests {
             Some(1e6 as i32),
             Some(chrono::Utc::now().timestamp() as u32),
         );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
 
-        // create new user
-        let issuer = User::new(
-            Uuid::new_v4(),
-            String::from("John"),
-            String::from("john.doe@gmail.com"),
-            String::from("password"),
-            Some(1e6 as f32),
-        );
+        // empty book has no top of book
+        assert_eq!(o_book.best_bid(), None);
+        assert_eq!(o_book.spread(), None);
+        assert_eq!(o_book.mid(), None);
 
-        // add stock to exchange
-        exchange.add_stock(stock, issuer);
+        // "100.00" sorts before "99.50" as a string, but not as a price
+        for price in [99.5, 100.0, 9.75] {
+            let bids = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                100,
+                Some(price),
+                Some(0.0),
+            );
+            o_book.add_order(bids[0].clone()).unwrap();
+        }
+        for price in [100.5, 101.25] {
+            let asks = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::ASK,
+                OrderType::LIMIT,
+                100,
+                Some(price),
+                Some(0.0),
+            );
+            o_book.add_order(asks[0].clone()).unwrap();
+        }
 
-        // test number of stocks in exchange
-        assert_eq!(exchange.stocks.len(), 1);
+        assert_eq!(o_book.best_bid(), Some(stock.price(100.0)));
+        assert_eq!(o_book.best_ask(), Some(stock.price(100.5)));
+        assert_eq!(o_book.spread(), Some(stock.price(0.5)));
+        assert_eq!(o_book.mid(), Some(stock.price(100.25)));
+        let ladder: Vec<Price> = o_book.bid_price_levels.keys().rev().copied().collect();
+        assert_eq!(
+            ladder,
+            vec![stock.price(100.0), stock.price(99.5), stock.price(9.75)]
+        );
 
-        // test to check users and userstocks
-        assert_eq!(exchange.users.len(), 1);
-        assert_eq!(exchange.user_stocks.len(), 1);
+        // an aggressive ask hits the highest bid first
+        let asks = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            150,
+            Some(99.0),
+            Some(0.0),
+        );
+        o_book.match_order(asks[0].clone()).unwrap();
+        assert_eq!(o_book.last_market_price, Some(stock.price(99.5)));
+        assert_eq!(o_book.best_bid(), Some(stock.price(99.5)));
+        let p_level = o_book
+            .get_price_level(OrderSide::BID, stock.price(99.5))
+            .cloned()
+            .unwrap();
+        assert_eq!(p_level.qty, 50);
+        assert!(o_book
+            .get_price_level(OrderSide::BID, stock.price(100.0))
+            .is_none());
+
+        assert_eq!(o_book.mid(), Some(stock.price(100.0)));
+        // odd tick sums get an extra decimal place instead of being rounded
+        let half = (stock.price(99.5) + stock.price(100.25)).half().unwrap();
+        assert_eq!(half, Price::new(99875, 3));
+        o_book.print_orderbook();
     }
 
-    // test executing order in exchange
+    // test tick size, lot size and order size limits on a stock
     #[test]
-    fn test_exchange_execute_order() {
-        // create new exchange
-        let mut exchange = Exchange::new();
-
-        // create new stock
-        let stock = Stock::new(
+    fn test_stock_trading_constraints() {
+        // create new stock with a 0.05 tick and 10 share lots
+        let mut stock = Stock::new(
             Uuid::new_v4(),
             String::from("Apple"),
             String::from("AAPL"),
@@ -1062,20 +1004,3034 @@ mod tests {
             Some(1e6 as i32),
             Some(chrono::Utc::now().timestamp() as u32),
         );
+        stock.tick_size = stock.price(0.05);
+        stock.lot_size = 10;
+        stock.min_order_qty = Some(10);
+        stock.max_order_qty = Some(1000);
+        stock.min_notional = Some(stock.price(100.0));
+        stock.max_notional = Some(stock.price(50000.0));
+        assert!(stock.validate().is_ok());
+
+        let new_order = |qty: i32, price: f64| {
+            Order::new(
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                stock.clone(),
+                OrderSide::BID,
+                OrderType::LIMIT,
+                qty,
+                chrono::Utc::now().timestamp() as u32,
+                Some(stock.price(price)),
+            )
+            .unwrap()
+        };
 
-        // create new user
-        let issuer = User::new(
-            Uuid::new_v4(),
-            String::from("John"),
-            String::from("john.doe@gmail.com"),
-            String::from("password"),
-            Some(1e6 as f32),
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        assert_eq!(
+            o_book.add_order(new_order(10, 10.03)),
+            Err(OrderError::PriceNotOnTick)
         );
-
-        // add stock to exchange
-        exchange.add_stock(stock.clone(), issuer);
-
-        // create new order
+        assert_eq!(
+            o_book.add_order(new_order(15, 10.05)),
+            Err(OrderError::QuantityNotOnLot)
+        );
+        assert_eq!(
+            o_book.add_order(new_order(2000, 10.0)),
+            Err(OrderError::QuantityAboveMaximum)
+        );
+        assert_eq!(
+            o_book.add_order(new_order(10, 5.0)),
+            Err(OrderError::NotionalBelowMinimum)
+        );
+        assert_eq!(
+            o_book.add_order(new_order(1000, 60.0)),
+            Err(OrderError::NotionalAboveMaximum)
+        );
+        assert!(o_book.add_order(new_order(20, 10.05)).is_ok());
+        assert_eq!(o_book.oid_map.len(), 1);
+
+        // a notional too large to represent is above the maximum rather than a panic
+        let mut order = new_order(1000, 10.0);
+        order.price = Some(Price::new(i64::MAX / 100 / 5 * 5, 2));
+        assert_eq!(
+            o_book.add_order(order),
+            Err(OrderError::NotionalAboveMaximum)
+        );
+
+        // the exchange rejects before the order is queued
+        let mut exchange = Exchange::new();
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        assert_eq!(
+            exchange.execute_order(new_order(10, 10.01)).unwrap_err(),
+            OrderError::PriceNotOnTick
+        );
+        let book = exchange
+            .orderbooks
+            .get(&stock.stock_id.to_string())
+            .unwrap();
+        assert_eq!(book.order_queue.len(), 0);
+        assert_eq!(book.oid_map.len(), 0);
+
+        // queued orders are held to the same constraints
+        assert_eq!(
+            exchange
+                .orderbooks
+                .get_mut(&stock.stock_id.to_string())
+                .unwrap()
+                .queue_order(new_order(15, 10.05)),
+            Err(OrderError::QuantityNotOnLot)
+        );
+        assert!(exchange.execute_all_orders().unwrap().is_empty());
+
+        // a price quoted at the wrong scale is rejected before it's compared with the tick size
+        let mut order = new_order(10, 10.0);
+        order.price = Some(Price::new(1, 30));
+        assert_eq!(
+            exchange.execute_order(order).unwrap_err(),
+            OrderError::InvalidPrice
+        );
+
+        // inconsistent limits are rejected on the stock itself
+        stock.min_order_qty = Some(5000);
+        assert!(stock.validate().is_err());
+        stock.min_order_qty = Some(10);
+        stock.price_scale = MAX_PRICE_SCALE + 1;
+        assert!(stock.validate().is_err());
+    }
+
+    // test that sweeping several resting orders reports one fill per counterparty
+    #[test]
+    fn test_multi_fill_execution() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+
+        // two asks at 10.00 and one at 10.50
+        let mut asks = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            50,
+            Some(10.0),
+            Some(0.0),
+        );
+        asks.extend(gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            50,
+            Some(10.5),
+            Some(0.0),
+        ));
+        for ask in asks.clone() {
+            o_book.add_order(ask).unwrap();
+        }
+
+        // a bid for 120 sweeps both levels and partially fills the last ask
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            120,
+            Some(11.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let exec = o_book.match_order(bid.clone()).unwrap();
+
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.fills.len(), 3);
+        assert_eq!(exec.filled_qty(), 120);
+        for (fill, ask) in exec.fills.iter().zip(asks.iter()) {
+            assert_eq!(fill.maker_order_id, ask.order_id);
+            assert_eq!(fill.maker_id, ask.creator_id);
+            assert_eq!(fill.taker_order_id, bid.order_id);
+            assert_eq!(fill.aggressor_side, OrderSide::BID);
+            assert_eq!(fill.price, ask.price.unwrap());
+        }
+        assert_eq!(exec.fills[2].qty, 20);
+        assert_ne!(exec.fills[0].trade_id, exec.fills[1].trade_id);
+
+        // the partially filled ask keeps its remaining 30
+        assert_eq!(o_book.oid_map.get(&asks[2].order_id).unwrap().qty, 30);
+        assert_eq!(
+            o_book
+                .get_price_level(OrderSide::ASK, stock.price(10.5))
+                .unwrap()
+                .qty,
+            30
+        );
+
+        // fills survive serialization for downstream consumers
+        let json = serde_json::to_string(&exec).unwrap();
+        let decoded: Execution = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.fills, exec.fills);
+    }
+
+    // test immediate-or-cancel and fill-or-kill time in force
+    #[test]
+    fn test_time_in_force() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+
+        // 100 offered at 10.00 and 100 at 10.50
+        let asks = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.5),
+        );
+        for ask in asks {
+            o_book.add_order(ask).unwrap();
+        }
+
+        // IOC bid for 150 at 10.00 fills 100 and cancels the remaining 50
+        let mut ioc = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            150,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        ioc.time_in_force = TimeInForce::IOC;
+        let exec = o_book.match_order(ioc.clone()).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert_eq!(exec.filled_qty(), 100);
+        assert_eq!(exec.order.qty, 50);
+        assert!(!o_book.oid_map.contains_key(&ioc.order_id));
+        assert_eq!(o_book.bid_price_levels.len(), 0);
+
+        // FOK bid for 150 at 10.50 can't fill in full and leaves the book untouched
+        let mut fok = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            150,
+            Some(10.5),
+            Some(0.0),
+        )[0]
+        .clone();
+        fok.time_in_force = TimeInForce::FOK;
+        assert_eq!(
+            o_book.match_order(fok.clone()),
+            Err(OrderError::InsufficientLiquidity)
+        );
+        assert_eq!(o_book.oid_map.len(), 1);
+        assert_eq!(
+            o_book
+                .get_price_level(OrderSide::ASK, stock.price(10.5))
+                .unwrap()
+                .qty,
+            100
+        );
+
+        // FOK for exactly the available qty fills
+        fok.qty = 100;
+        let exec = o_book.match_order(fok).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.filled_qty(), 100);
+        assert_eq!(o_book.oid_map.len(), 0);
+    }
+
+    // test stop and stop-limit orders waiting in the trigger book
+    #[test]
+    fn test_stop_orders() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+
+        // 100 offered at each of 10.00, 10.50 and 11.00
+        let asks = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.5),
+        );
+        for ask in asks {
+            o_book.add_order(ask).unwrap();
+        }
+
+        // buy stop at 10.50, and a sell stop-limit that must not trigger
+        let buy_stop = gen_stop_order(
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::STOP,
+            50,
+            None,
+            Some(10.5),
+            None,
+        );
+        let sell_stop = gen_stop_order(
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::STOP_LIMIT,
+            50,
+            Some(9.0),
+            Some(9.5),
+            None,
+        );
+        assert!(buy_stop.validate().is_ok());
+        assert!(sell_stop.validate().is_ok());
+
+        o_book.queue_order(buy_stop.clone()).unwrap();
+        o_book.queue_order(sell_stop.clone()).unwrap();
+        let execs = o_book.execute_all_orders().unwrap();
+        assert_eq!(execs.len(), 2);
+        assert_eq!(o_book.trigger_book.len(), 2);
+        // stop orders are not visible in the book
+        assert_eq!(o_book.oid_map.len(), 3);
+        assert_eq!(o_book.bid_price_levels.len(), 0);
+
+        // a bid trading through 10.50 releases the buy stop, which then lifts 10.50
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            150,
+            Some(10.5),
+            Some(0.0),
+        );
+        o_book.queue_order(bid[0].clone()).unwrap();
+        let execs = o_book.execute_all_orders().unwrap();
+        let exec_types: Vec<ExecutionType> = execs.iter().map(|e| e.exec_type.clone()).collect();
+        assert_eq!(
+            exec_types,
+            vec![
+                ExecutionType::MATCH,
+                ExecutionType::TRIGGER,
+                ExecutionType::MATCH
+            ]
+        );
+        assert_eq!(execs[1].order.order_id, buy_stop.order_id);
+        assert_eq!(execs[1].order.order_type, OrderType::MARKET);
+        assert_eq!(execs[2].fills[0].price, stock.price(10.5));
+        assert_eq!(o_book.trigger_book.len(), 1);
+        assert!(o_book.trigger_book.contains(&sell_stop.order_id));
+
+        // untriggered stops can be deleted
+        o_book.delete_order(sell_stop.order_id).unwrap();
+        assert!(o_book.trigger_book.is_empty());
+        assert!(o_book.trigger_book.ask_triggers.is_empty());
+
+        // a stop already through its trigger is activated straight away
+        let mut late_stop = buy_stop.clone();
+        late_stop.order_id = Uuid::new_v4();
+        late_stop.stop_price = Some(stock.price(10.0));
+        let exec = o_book.match_order(late_stop).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(o_book.drain_events()[0].exec_type, ExecutionType::TRIGGER);
+
+        // stop orders need a stop price
+        let mut bad_stop = buy_stop.clone();
+        bad_stop.stop_price = None;
+        assert_eq!(bad_stop.validate(), Err(OrderError::InvalidStopPrice));
+    }
+
+    // test iceberg orders only showing their display qty and replenishing from the reserve
+    #[test]
+    fn test_iceberg_orders() {
+        // create new exchange and stock
+        let mut exchange = Exchange::new();
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+
+        // iceberg ask for 300 showing 100, followed by a plain ask for 100
+        let asks = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        );
+        let mut iceberg = asks[0].clone();
+        iceberg.qty = 300;
+        iceberg.display_qty = Some(100);
+        let plain = asks[1].clone();
+        exchange.execute_order(iceberg.clone()).unwrap();
+        exchange.execute_order(plain.clone()).unwrap();
+
+        let key = stock.stock_id.to_string();
+        let level = exchange.orderbooks[&key]
+            .ask_price_levels
+            .get(&stock.price(10.0))
+            .cloned()
+            .unwrap();
+        assert_eq!(level.qty, 200);
+        let json: serde_json::Value =
+            serde_json::from_str(&exchange.get_orderbook_json(stock.stock_id)).unwrap();
+        assert_eq!(json["ask_price_levels"][0]["qty"], 200);
+
+        // taking the visible slice replenishes it at the back of the queue
+        let bids = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            150,
+            Some(10.0),
+            Some(0.0),
+        );
+        let exec = exchange.execute_order(bids[0].clone()).unwrap();
+        assert_eq!(exec.fills.len(), 2);
+        assert_eq!(exec.fills[0].maker_order_id, iceberg.order_id);
+        assert_eq!(exec.fills[0].qty, 100);
+        assert_eq!(exec.fills[1].maker_order_id, plain.order_id);
+        assert_eq!(exec.fills[1].qty, 50);
+
+        let o_book = &exchange.orderbooks[&key];
+        let level = o_book.ask_price_levels.get(&stock.price(10.0)).unwrap();
+        assert_eq!(level.qty, 150);
+        assert_eq!(
+            level.orders,
+            vec![plain.order_id, iceberg.order_id]
+                .into_iter()
+                .collect::<std::collections::VecDeque<Uuid>>()
+        );
+        let resting = &o_book.oid_map[&iceberg.order_id];
+        assert_eq!(resting.qty, 200);
+        assert_eq!(resting.visible_qty(), 100);
+
+        // a larger bid keeps trading the same level as the iceberg replenishes
+        let mut big_bid = bids[1].clone();
+        big_bid.qty = 250;
+        let exec = exchange.execute_order(big_bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        let fill_qtys: Vec<i32> = exec.fills.iter().map(|f| f.qty).collect();
+        assert_eq!(fill_qtys, vec![50, 100, 100]);
+        assert_eq!(exchange.orderbooks[&key].ask_price_levels.len(), 0);
+        assert_eq!(exchange.orderbooks[&key].oid_map.len(), 0);
+
+        // market icebergs make no sense
+        let mut bad = bids[0].clone();
+        bad.order_type = OrderType::MARKET;
+        bad.display_qty = Some(10);
+        assert_eq!(bad.validate(), Err(OrderError::InvalidDisplayQuantity));
+    }
+
+    // test post-only orders never taking liquidity
+    #[test]
+    fn test_post_only_orders() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+
+        // 100 offered at 10.00, 100 bid at 9.90
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        );
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(9.9),
+            Some(0.0),
+        );
+        o_book.add_order(ask[0].clone()).unwrap();
+        o_book.add_order(bid[0].clone()).unwrap();
+
+        // a post-only bid at the ask would take liquidity and is rejected
+        let mut maker_bids = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            50,
+            Some(10.0),
+            Some(-0.05),
+        );
+        maker_bids[0].post_only = Some(PostOnly::REJECT);
+        assert_eq!(
+            o_book.match_order(maker_bids[0].clone()),
+            Err(OrderError::PostOnlyWouldCross)
+        );
+        assert_eq!(o_book.oid_map.len(), 2);
+
+        // with repricing it rests one tick below the best ask instead
+        maker_bids[0].post_only = Some(PostOnly::REPRICE);
+        let exec = o_book.match_order(maker_bids[0].clone()).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::ADD);
+        assert!(exec.fills.is_empty());
+        assert_eq!(exec.order.price, Some(stock.price(9.99)));
+        assert_eq!(o_book.best_bid(), Some(stock.price(9.99)));
+
+        // a post-only bid that doesn't cross rests at its own price
+        maker_bids[1].post_only = Some(PostOnly::REJECT);
+        let exec = o_book.match_order(maker_bids[1].clone()).unwrap();
+        assert_eq!(exec.order.price, Some(stock.price(9.95)));
+        assert_eq!(o_book.oid_map.len(), 4);
+
+        // post-only needs a limit price
+        maker_bids[2].order_type = OrderType::MARKET;
+        maker_bids[2].post_only = Some(PostOnly::REJECT);
+        assert_eq!(maker_bids[2].validate(), Err(OrderError::InvalidPrice));
+    }
+
+    // test self-trade prevention modes keyed on creator_id
+    #[test]
+    fn test_self_trade_prevention() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let creator = Uuid::new_v4();
+
+        // book with the creator's own ask ahead of someone else's, both 50 @ 10.00
+        let setup = |stp: SelfTradePrevention| {
+            let mut o_book = orderbook::OrderBook::new(stock.clone());
+            let mut asks = gen_orders(
+                stock.clone(),
+                2,
+                OrderSide::ASK,
+                OrderType::LIMIT,
+                50,
+                Some(10.0),
+                Some(0.0),
+            );
+            asks[0].creator_id = creator;
+            for ask in asks.clone() {
+                o_book.add_order(ask).unwrap();
+            }
+            let mut bid = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                80,
+                Some(10.0),
+                Some(0.0),
+            )[0]
+            .clone();
+            bid.creator_id = creator;
+            bid.self_trade_prevention = Some(stp);
+            (o_book, asks, bid)
+        };
+
+        // cancel newest: the incoming order is dropped, the book is untouched
+        let (mut o_book, _, bid) = setup(SelfTradePrevention::CANCEL_NEWEST);
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert_eq!(exec.prevented_qty, 50);
+        assert!(exec.fills.is_empty());
+        assert_eq!(o_book.oid_map.len(), 2);
+
+        // cancel oldest: the creator's ask is cancelled and matching carries on
+        let (mut o_book, asks, bid) = setup(SelfTradePrevention::CANCEL_OLDEST);
+        let exec = o_book.match_order(bid.clone()).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::ADD);
+        assert_eq!(exec.prevented_qty, 50);
+        assert_eq!(exec.fills.len(), 1);
+        assert_eq!(exec.fills[0].maker_order_id, asks[1].order_id);
+        assert_eq!(o_book.oid_map[&bid.order_id].qty, 30);
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::CANCEL);
+        assert_eq!(events[0].order.order_id, asks[0].order_id);
+
+        // cancel both: nothing trades and both orders are gone
+        let (mut o_book, asks, bid) = setup(SelfTradePrevention::CANCEL_BOTH);
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert!(exec.fills.is_empty());
+        assert_eq!(o_book.oid_map.len(), 1);
+        assert!(o_book.oid_map.contains_key(&asks[1].order_id));
+
+        // decrement and cancel: 80 - 50 leaves 30 to trade with the other ask
+        let (mut o_book, asks, bid) = setup(SelfTradePrevention::DECREMENT_AND_CANCEL);
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.prevented_qty, 50);
+        assert_eq!(exec.filled_qty(), 30);
+        assert_eq!(o_book.oid_map.len(), 1);
+        assert_eq!(o_book.oid_map[&asks[1].order_id].qty, 20);
+
+        // a resting order that's only decremented is reported as a MODIFY, so the exchange
+        // can follow it through to being filled
+        let mut exchange = Exchange::new();
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let mut ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        ask.creator_id = creator;
+        exchange.execute_order(ask.clone()).unwrap();
+        let (_, _, mut bid) = setup(SelfTradePrevention::DECREMENT_AND_CANCEL);
+        bid.qty = 40;
+        let exec = exchange.execute_order(bid).unwrap();
+        assert_eq!(exec.prevented_qty, 40);
+        let events = exchange
+            .orderbooks
+            .get_mut(&stock.stock_id.to_string())
+            .unwrap()
+            .drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::MODIFY);
+        assert_eq!(events[0].order.qty, 60);
+        assert_eq!(
+            exchange.get_order_status(ask.order_id).unwrap().order.qty,
+            60
+        );
+
+        let taker = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            60,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        exchange.execute_order(taker).unwrap();
+        let state = exchange.get_order_status(ask.order_id).unwrap();
+        assert_eq!(state.status, OrderStatus::FILLED);
+        assert_eq!(state.filled_qty, 60);
+        assert_eq!(exchange.get_order_stock(ask.order_id), None);
+
+        // fill-or-kill stays all or nothing when the creator's own orders are in its way,
+        // unless they're the ones that get cancelled
+        for stp in [
+            SelfTradePrevention::CANCEL_NEWEST,
+            SelfTradePrevention::CANCEL_BOTH,
+            SelfTradePrevention::DECREMENT_AND_CANCEL,
+        ] {
+            let (mut o_book, _, mut bid) = setup(stp);
+            bid.qty = 50;
+            bid.time_in_force = TimeInForce::FOK;
+            assert_eq!(
+                o_book.match_order(bid),
+                Err(OrderError::InsufficientLiquidity)
+            );
+            assert_eq!(o_book.oid_map.len(), 2);
+            assert!(o_book.drain_events().is_empty());
+        }
+        let (mut o_book, asks, mut bid) = setup(SelfTradePrevention::CANCEL_OLDEST);
+        bid.qty = 50;
+        bid.time_in_force = TimeInForce::FOK;
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.fills[0].maker_order_id, asks[1].order_id);
+    }
+
+    // test call auctions accumulating orders and uncrossing at a single price
+    #[test]
+    fn test_call_auction() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        o_book.set_clock(clock.clone());
+        o_book.last_market_price = Some(stock.price(10.0));
+        o_book.start_auction();
+
+        // bids 100 @ 10.10 and 100 @ 10.00, asks 150 @ 9.95 and 50 @ 10.05
+        let bids = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(10.1),
+            Some(-0.1),
+        );
+        let mut asks = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            50,
+            Some(9.95),
+            Some(0.1),
+        );
+        asks[0].qty = 150;
+
+        // crossing orders rest without matching, each reporting the indicative state
+        let exec = o_book.match_order(bids[0].clone()).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::ADD);
+        assert_eq!(exec.auction.unwrap().price, None);
+        o_book.match_order(bids[1].clone()).unwrap();
+        o_book.match_order(asks[0].clone()).unwrap();
+        let exec = o_book.match_order(asks[1].clone()).unwrap();
+        assert!(exec.fills.is_empty());
+        assert_eq!(o_book.oid_map.len(), 4);
+
+        // 9.95 and 10.00 both execute 150 with 50 left over, 10.00 is closest to the last trade
+        let state = exec.auction.unwrap();
+        assert_eq!(state.price, Some(stock.price(10.0)));
+        assert_eq!(state.volume, 150);
+        assert_eq!(state.imbalance, 50);
+        assert_eq!(state.imbalance_side, Some(OrderSide::BID));
+        assert_eq!(o_book.auction_state(), state);
+
+        // nothing can execute immediately during an auction
+        let mut ioc = bids[0].clone();
+        ioc.order_id = Uuid::new_v4();
+        ioc.time_in_force = TimeInForce::IOC;
+        assert_eq!(o_book.match_order(ioc), Err(OrderError::InvalidTimeInForce));
+
+        // uncross: both bids trade against the 9.95 ask at 10.00, timed by the book's clock
+        clock.advance(30);
+        let execs = o_book.uncross().unwrap();
+        assert_eq!(execs.len(), 2);
+        assert!(execs
+            .iter()
+            .all(|exec| exec.exec_type == ExecutionType::UNCROSS && exec.time_executed == 1_030));
+        assert!(execs
+            .iter()
+            .flat_map(|exec| exec.fills.iter())
+            .all(|fill| fill.price == stock.price(10.0)));
+        assert_eq!(execs[0].order.order_id, bids[0].order_id);
+        assert_eq!(execs[0].filled_qty(), 100);
+        assert_eq!(execs[1].filled_qty(), 50);
+
+        // the leftover bid and the non-crossing ask stay on the book
+        assert_eq!(o_book.trading_phase, TradingPhase::CONTINUOUS);
+        assert_eq!(o_book.last_market_price, Some(stock.price(10.0)));
+        assert_eq!(o_book.oid_map.len(), 2);
+        assert_eq!(o_book.oid_map[&bids[1].order_id].qty, 50);
+        assert_eq!(o_book.best_bid(), Some(stock.price(10.0)));
+        assert_eq!(o_book.best_ask(), Some(stock.price(10.05)));
+
+        // all-or-none and minimum qty orders only trade if their share of the uncross meets
+        // them, the 40 bid skips both and trades with the plain ask behind them
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        o_book.start_auction();
+        let mut asks = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(9.9),
+            Some(0.0),
+        );
+        asks[0].all_or_none = true;
+        asks[1].min_qty = Some(60);
+        asks[2].qty = 40;
+        let mut bid = bids[0].clone();
+        bid.order_id = Uuid::new_v4();
+        bid.qty = 40;
+        for order in asks.iter().chain([&bid]) {
+            o_book.match_order(order.clone()).unwrap();
+        }
+        assert_eq!(o_book.auction_state().volume, 40);
+        let execs = o_book.uncross().unwrap();
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].fills.len(), 1);
+        assert_eq!(execs[0].fills[0].maker_order_id, asks[2].order_id);
+        assert_eq!(o_book.oid_map[&asks[0].order_id].qty, 100);
+        assert_eq!(o_book.oid_map[&asks[1].order_id].qty, 100);
+        assert!(!o_book.oid_map.contains_key(&asks[2].order_id));
+    }
+
+    // test GTD and DAY orders expiring off the book
+    #[test]
+    fn test_order_expiry() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+
+        // 2023-11-14 22:13:20 UTC, the day ends 6400s later
+        let start: u32 = 1_700_000_000;
+        let clock = Arc::new(ManualClock::new(start));
+        o_book.set_clock(clock.clone());
+
+        // GTC, GTD (one minute), DAY and a GTD stop
+        let mut bids = gen_orders(
+            stock.clone(),
+            4,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(-0.1),
+        );
+        bids[3] = gen_stop_order(
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::STOP_LIMIT,
+            100,
+            Some(9.7),
+            Some(10.5),
+            None,
+        );
+        for bid in bids.iter_mut() {
+            bid.time_created = start;
+        }
+        bids[1].time_in_force = TimeInForce::GTD;
+        bids[1].expire_time = Some(start + 60);
+        bids[2].time_in_force = TimeInForce::DAY;
+        bids[3].time_in_force = TimeInForce::GTD;
+        bids[3].expire_time = Some(start + 60);
+        for bid in bids.clone() {
+            o_book.match_order(bid).unwrap();
+        }
+        assert_eq!(o_book.oid_map.len(), 3);
+        assert_eq!(o_book.trigger_book.len(), 1);
+
+        // nothing has expired yet
+        clock.advance(59);
+        assert!(o_book.expire_orders().is_empty());
+
+        // the GTD orders expire, resting or not
+        clock.advance(1);
+        let execs = o_book.expire_orders();
+        assert_eq!(execs.len(), 2);
+        assert!(execs.iter().all(
+            |exec| exec.exec_type == ExecutionType::EXPIRE && exec.time_executed == start + 60
+        ));
+        assert!(!o_book.oid_map.contains_key(&bids[1].order_id));
+        assert!(o_book.trigger_book.is_empty());
+        assert!(o_book
+            .get_price_level(OrderSide::BID, stock.price(9.9))
+            .is_none());
+
+        // the DAY order expires at midnight, the GTC order stays
+        clock.set(start + 6400);
+        let execs = o_book.expire_orders();
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].order.order_id, bids[2].order_id);
+        assert_eq!(o_book.oid_map.len(), 1);
+        assert!(o_book.oid_map.contains_key(&bids[0].order_id));
+
+        // orders that have already expired are rejected
+        let mut late = bids[1].clone();
+        late.order_id = Uuid::new_v4();
+        assert_eq!(
+            o_book.match_order(late.clone()),
+            Err(OrderError::OrderExpired)
+        );
+
+        // GTD orders need an expiry after their creation
+        late.expire_time = None;
+        assert_eq!(late.validate(), Err(OrderError::InvalidExpireTime));
+
+        // a DAY order's day comes from the clock, not its creation time
+        for time_created in [u32::MAX, start - 86400] {
+            let mut day = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                100,
+                Some(9.5),
+                Some(0.0),
+            )
+            .remove(0);
+            day.time_in_force = TimeInForce::DAY;
+            day.time_created = time_created;
+            assert!(day.validate().is_ok());
+            let exec = o_book.match_order(day).unwrap();
+            assert_eq!(exec.exec_type, ExecutionType::ADD);
+            assert_eq!(exec.order.expire_time, Some(start + 6400 + 86400));
+        }
+        assert_eq!(Order::end_of_day(u32::MAX), u32::MAX);
+    }
+
+    // test trailing stops ratcheting with the last market price
+    #[test]
+    fn test_trailing_stop_orders() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        let trade = |o_book: &mut OrderBook, price: f64| {
+            let ask = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::ASK,
+                OrderType::LIMIT,
+                100,
+                Some(price),
+                Some(0.0),
+            );
+            let bid = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                100,
+                Some(price),
+                Some(0.0),
+            );
+            o_book.match_order(bid[0].clone()).unwrap();
+            o_book.match_order(ask[0].clone()).unwrap();
+        };
+
+        // a trailing stop needs a reference price and an offset
+        let no_offset = Order::new_stop(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::TRAILING_STOP,
+            50,
+            chrono::Utc::now().timestamp() as u32,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(no_offset.err(), Some(OrderError::InvalidTrailingOffset));
+        let sell_trail = gen_stop_order(
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::TRAILING_STOP,
+            50,
+            None,
+            None,
+            Some(TrailingOffset::AMOUNT(stock.price(0.5))),
+        );
+        assert_eq!(
+            o_book.match_order(sell_trail.clone()),
+            Err(OrderError::InvalidStopPrice)
+        );
+
+        // 200 bid at 9.00, last trade at 10.00
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            200,
+            Some(9.0),
+            Some(0.0),
+        );
+        o_book.add_order(bid[0].clone()).unwrap();
+        o_book.last_market_price = Some(stock.price(10.0));
+
+        // sell trailing 0.50 below, buy stop-limit trailing 5% above with its limit 0.10 higher
+        o_book.match_order(sell_trail.clone()).unwrap();
+        let buy_trail = gen_stop_order(
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::TRAILING_STOP_LIMIT,
+            50,
+            Some(10.6),
+            None,
+            Some(TrailingOffset::BASIS_POINTS(500)),
+        );
+        o_book.match_order(buy_trail.clone()).unwrap();
+        let stop_of = |o_book: &OrderBook, order_id: Uuid| {
+            let order = &o_book.trigger_book.orders[&order_id];
+            (order.stop_price, order.price)
+        };
+        assert_eq!(
+            stop_of(&o_book, sell_trail.order_id),
+            (Some(stock.price(9.5)), None)
+        );
+        assert_eq!(
+            stop_of(&o_book, buy_trail.order_id),
+            (Some(stock.price(10.5)), Some(stock.price(10.6)))
+        );
+
+        // the sell stop follows the price up, the buy stop never moves away from it
+        trade(&mut o_book, 10.4);
+        assert_eq!(
+            stop_of(&o_book, sell_trail.order_id).0,
+            Some(stock.price(9.9))
+        );
+        assert_eq!(
+            stop_of(&o_book, buy_trail.order_id).0,
+            Some(stock.price(10.5))
+        );
+        trade(&mut o_book, 10.2);
+        assert_eq!(
+            stop_of(&o_book, sell_trail.order_id).0,
+            Some(stock.price(9.9))
+        );
+
+        // a trade at 9.90 triggers the sell stop and drags the buy stop down to 10.39
+        trade(&mut o_book, 9.9);
+        assert!(!o_book.trigger_book.contains(&sell_trail.order_id));
+        assert_eq!(
+            stop_of(&o_book, buy_trail.order_id),
+            (Some(stock.price(10.39)), Some(stock.price(10.49)))
+        );
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::TRIGGER);
+        assert_eq!(events[0].order.order_type, OrderType::MARKET);
+
+        // the released market order sells into the 9.00 bid
+        let execs = o_book.execute_all_orders().unwrap();
+        assert_eq!(execs[0].order.order_id, sell_trail.order_id);
+        assert_eq!(execs[0].fills[0].price, stock.price(9.0));
+        assert_eq!(
+            stop_of(&o_book, buy_trail.order_id),
+            (Some(stock.price(9.45)), Some(stock.price(9.55)))
+        );
+    }
+
+    // test pegged orders following the top of book
+    #[test]
+    fn test_pegged_orders() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        let price_of = |o_book: &OrderBook, order: &Order| o_book.oid_map[&order.order_id].price;
+
+        // pegs need something to reference
+        let mut bid_peg = gen_pegged_order(
+            stock.clone(),
+            OrderSide::BID,
+            100,
+            PegReference::PRIMARY,
+            Some(0.01),
+        );
+        assert_eq!(
+            o_book.match_order(bid_peg.clone()),
+            Err(OrderError::PegReferenceUnavailable)
+        );
+
+        // 9.90 bid, 10.10 offer
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(9.9),
+            Some(0.0),
+        );
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.1),
+            Some(0.0),
+        );
+        o_book.add_order(bid[0].clone()).unwrap();
+        o_book.add_order(ask[0].clone()).unwrap();
+
+        // primary bid a tick above the best bid, a midpoint ask, and an ask 0.15 over the bid
+        let ask_pegs = [
+            gen_pegged_order(
+                stock.clone(),
+                OrderSide::ASK,
+                100,
+                PegReference::MIDPOINT,
+                None,
+            ),
+            gen_pegged_order(
+                stock.clone(),
+                OrderSide::ASK,
+                100,
+                PegReference::MARKET,
+                Some(0.15),
+            ),
+        ];
+        let exec = o_book.match_order(bid_peg.clone()).unwrap();
+        assert_eq!(exec.order.price, Some(stock.price(9.91)));
+        o_book.match_order(ask_pegs[0].clone()).unwrap();
+        o_book.match_order(ask_pegs[1].clone()).unwrap();
+        assert_eq!(price_of(&o_book, &ask_pegs[0]), Some(stock.price(10.0)));
+        assert_eq!(price_of(&o_book, &ask_pegs[1]), Some(stock.price(10.05)));
+        assert!(o_book.drain_events().is_empty());
+
+        // a new best bid moves every peg, the midpoint rounding up to 10.03
+        let new_bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(9.95),
+            Some(0.0),
+        );
+        o_book.match_order(new_bid[0].clone()).unwrap();
+        assert_eq!(price_of(&o_book, &bid_peg), Some(stock.price(9.96)));
+        assert_eq!(price_of(&o_book, &ask_pegs[0]), Some(stock.price(10.03)));
+        assert_eq!(price_of(&o_book, &ask_pegs[1]), Some(stock.price(10.1)));
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 3);
+        assert!(events
+            .iter()
+            .all(|event| event.exec_type == ExecutionType::MODIFY));
+        assert!(o_book
+            .get_price_level(OrderSide::BID, stock.price(9.91))
+            .is_none());
+
+        // and pulling it moves them back
+        o_book.delete_order(new_bid[0].order_id).unwrap();
+        assert_eq!(price_of(&o_book, &bid_peg), Some(stock.price(9.91)));
+        assert_eq!(price_of(&o_book, &ask_pegs[0]), Some(stock.price(10.0)));
+        assert_eq!(price_of(&o_book, &ask_pegs[1]), Some(stock.price(10.05)));
+
+        // only limit orders can be pegged
+        bid_peg.order_type = OrderType::MARKET;
+        assert_eq!(bid_peg.validate(), Err(OrderError::InvalidPeg));
+    }
+
+    // test OCO and bracket order groups on the exchange
+    #[test]
+    fn test_order_groups() {
+        // create new exchange
+        let mut exchange = Exchange::new();
+
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let book_key = stock.stock_id.to_string();
+        let creator = Uuid::new_v4();
+        let order = |side: OrderSide, order_type: OrderType, qty: i32, price: Option<f64>| {
+            let mut order =
+                gen_orders(stock.clone(), 1, side, order_type, qty, price, Some(0.0))[0].clone();
+            order.creator_id = creator;
+            order
+        };
+
+        // 100 offered at 10.00 by someone else
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        );
+        exchange.execute_order(ask[0].clone()).unwrap();
+
+        // exits must be on the other side of the entry
+        let entry = order(OrderSide::BID, OrderType::LIMIT, 100, Some(10.0));
+        let take_profit = order(OrderSide::ASK, OrderType::LIMIT, 100, Some(11.0));
+        let mut stop_loss = gen_stop_order(
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::STOP,
+            100,
+            None,
+            Some(9.5),
+            None,
+        );
+        stop_loss.creator_id = creator;
+        assert_eq!(
+            exchange.submit_bracket(entry.clone(), take_profit.clone(), entry.clone()),
+            Err(OrderError::InvalidOrderGroup)
+        );
+
+        // the entry fills and arms both exits
+        let execs = exchange
+            .submit_bracket(entry.clone(), take_profit.clone(), stop_loss.clone())
+            .unwrap();
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].exec_type, ExecutionType::MATCH);
+        let group_id = execs[0].group_id.unwrap();
+        let events = exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|e| e.exec_type == ExecutionType::ADD && e.group_id == Some(group_id)));
+        assert!(exchange.get_order_group(group_id).unwrap().armed);
+
+        // 40 of the take-profit fills, the stop-loss is cut down to match
+        let bids = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            40,
+            Some(11.0),
+            Some(0.0),
+        );
+        exchange.execute_order(bids[0].clone()).unwrap();
+        let o_book = exchange.orderbooks.get_mut(&book_key).unwrap();
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::MODIFY);
+        assert_eq!(events[0].order.order_id, stop_loss.order_id);
+        assert_eq!(o_book.trigger_book.orders[&stop_loss.order_id].qty, 60);
+
+        // the rest of the take-profit fills and the stop-loss is cancelled
+        let mut last_bid = bids[1].clone();
+        last_bid.qty = 60;
+        exchange.execute_order(last_bid).unwrap();
+        let o_book = exchange.orderbooks.get_mut(&book_key).unwrap();
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::CANCEL);
+        assert!(o_book.trigger_book.is_empty());
+        assert!(exchange.get_order_group(group_id).is_none());
+
+        // pulling one leg of an OCO pair pulls the other
+        let limit_leg = order(OrderSide::ASK, OrderType::LIMIT, 50, Some(12.0));
+        let mut stop_leg = gen_stop_order(
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::STOP,
+            50,
+            None,
+            Some(9.0),
+            None,
+        );
+        stop_leg.creator_id = creator;
+        let execs = exchange
+            .submit_oco(limit_leg.clone(), stop_leg.clone())
+            .unwrap();
+        assert_eq!(execs.len(), 2);
+        exchange.delete_order(limit_leg.order_id).unwrap();
+        let o_book = exchange.orderbooks.get_mut(&book_key).unwrap();
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::CANCEL);
+        assert_eq!(events[0].order.order_id, stop_leg.order_id);
+        assert!(o_book.oid_map.is_empty());
+        assert!(o_book.trigger_book.is_empty());
+        assert!(exchange.order_groups.is_empty());
+    }
+
+    // test minimum qty and all-or-none constraints
+    #[test]
+    fn test_min_qty_and_all_or_none() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        let bid = |qty: i32, price: f64| {
+            let mut bid = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                qty,
+                Some(price),
+                Some(0.0),
+            )[0]
+            .clone();
+            bid.time_in_force = TimeInForce::IOC;
+            bid
+        };
+
+        // at 10.00: 100 all-or-none, 50 with a 40 minimum, then 30 plain
+        let mut asks = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            50,
+            Some(10.0),
+            Some(0.0),
+        );
+        asks[0].qty = 100;
+        asks[0].all_or_none = true;
+        asks[1].min_qty = Some(40);
+        asks[2].qty = 30;
+        for ask in asks.clone() {
+            o_book.add_order(ask).unwrap();
+        }
+
+        // 60 skips the all-or-none order, takes 50 and 10 of the 30
+        let exec = o_book.match_order(bid(60, 10.0)).unwrap();
+        assert_eq!(exec.fills.len(), 2);
+        assert_eq!(exec.fills[0].maker_order_id, asks[1].order_id);
+        assert_eq!(exec.fills[1].maker_order_id, asks[2].order_id);
+        assert_eq!(o_book.oid_map[&asks[2].order_id].qty, 20);
+
+        // the skipped order keeps its place at the front of the level
+        let p_level = o_book
+            .get_price_level(OrderSide::ASK, stock.price(10.0))
+            .unwrap();
+        assert_eq!(p_level.orders[0], asks[0].order_id);
+
+        // an incoming all-or-none order that can't fill in full doesn't trade
+        let mut aon_bid = bid(150, 10.0);
+        aon_bid.all_or_none = true;
+        let exec = o_book.match_order(aon_bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert!(exec.fills.is_empty());
+        assert_eq!(o_book.oid_map.len(), 2);
+
+        // but 120 can take both resting orders in full
+        let exec = o_book.match_order(bid(120, 10.0)).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.fills[0].qty, 100);
+        assert!(o_book.oid_map.is_empty());
+
+        // an incoming minimum of 50 skips the 20 at 10.01 and takes the 60 at 10.02
+        let more_asks = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            20,
+            Some(10.01),
+            Some(0.01),
+        );
+        o_book.add_order(more_asks[0].clone()).unwrap();
+        let mut big_ask = more_asks[1].clone();
+        big_ask.qty = 60;
+        o_book.add_order(big_ask.clone()).unwrap();
+        let mut min_bid = bid(80, 10.05);
+        min_bid.min_qty = Some(50);
+        let exec = o_book.match_order(min_bid.clone()).unwrap();
+        assert_eq!(exec.fills.len(), 1);
+        assert_eq!(exec.fills[0].maker_order_id, big_ask.order_id);
+        assert!(o_book.oid_map.contains_key(&more_asks[0].order_id));
+
+        // the minimum can't exceed the order
+        min_bid.min_qty = Some(100);
+        assert_eq!(min_bid.validate(), Err(OrderError::InvalidMinQuantity));
+    }
+
+    // test market orders walking the book within their protection limits
+    #[test]
+    fn test_market_order_protection() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+
+        // 100 offered at each of 10.00, 10.05 and 10.20, 100 bid at each of 9.95 and 9.90
+        let mut resting = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.05),
+        );
+        resting[2].price = Some(stock.price(10.2));
+        resting.extend(gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(9.95),
+            Some(0.05),
+        ));
+        resting[4].price = Some(stock.price(9.9));
+        for order in resting.clone() {
+            o_book.add_order(order).unwrap();
+        }
+
+        // a market bid limited to 10 ticks past 10.00 stops before 10.20
+        let mut market_bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::MARKET,
+            300,
+            None,
+            None,
+        )[0]
+        .clone();
+        market_bid.market_protection = Some(MarketProtection::TICKS(10));
+
+        // as fill-or-kill it can't fill in full
+        market_bid.time_in_force = TimeInForce::FOK;
+        assert_eq!(
+            o_book.match_order(market_bid.clone()),
+            Err(OrderError::InsufficientLiquidity)
+        );
+
+        market_bid.time_in_force = TimeInForce::GTC;
+        let exec = o_book.match_order(market_bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert_eq!(exec.filled_qty(), 200);
+        assert_eq!(exec.order.qty, 100);
+        assert_eq!(o_book.best_ask(), Some(stock.price(10.2)));
+
+        // 0.6% of 9.95 truncates to 0.05, so the limit is 9.90 and both bids trade
+        let mut market_ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::MARKET,
+            250,
+            None,
+            None,
+        )[0]
+        .clone();
+        market_ask.market_protection = Some(MarketProtection::BASIS_POINTS(60));
+        let exec = o_book.match_order(market_ask).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert_eq!(exec.filled_qty(), 200);
+        assert!(o_book.bid_price_levels.is_empty());
+
+        // without protection a market bid takes whatever is left and never rests
+        let market_bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::MARKET,
+            150,
+            None,
+            None,
+        )[0]
+        .clone();
+        let exec = o_book.match_order(market_bid.clone()).unwrap();
+        assert_eq!(exec.filled_qty(), 100);
+        assert!(o_book.oid_map.is_empty());
+
+        // protection only applies to market orders
+        let mut limit_bid = market_bid.clone();
+        limit_bid.order_type = OrderType::LIMIT;
+        limit_bid.price = Some(stock.price(10.0));
+        limit_bid.market_protection = Some(MarketProtection::TICKS(1));
+        assert_eq!(
+            limit_bid.validate(),
+            Err(OrderError::InvalidMarketProtection)
+        );
+    }
+
+    // test pro-rata allocation between orders resting at the same price
+    #[test]
+    fn test_pro_rata_allocation() {
+        // create new stock
+        let mut stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+
+        // book with asks of the given sizes resting at 10.00
+        let setup = |stock: &Stock, qtys: &[i32]| {
+            let mut o_book = orderbook::OrderBook::new(stock.clone());
+            let mut asks = gen_orders(
+                stock.clone(),
+                qtys.len(),
+                OrderSide::ASK,
+                OrderType::LIMIT,
+                100,
+                Some(10.0),
+                Some(0.0),
+            );
+            for (ask, qty) in asks.iter_mut().zip(qtys) {
+                ask.qty = *qty;
+                o_book.add_order(ask.clone()).unwrap();
+            }
+            (o_book, asks)
+        };
+        let bid = |stock: &Stock, qty: i32| {
+            gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                qty,
+                Some(10.0),
+                Some(0.0),
+            )[0]
+            .clone()
+        };
+        let fill_qtys = |exec: &Execution| -> Vec<(Uuid, i32)> {
+            exec.fills
+                .iter()
+                .map(|fill| (fill.maker_order_id, fill.qty))
+                .collect()
+        };
+
+        // 1. shares are in proportion to each order's size
+        stock.allocation = Allocation::PRO_RATA;
+        let (mut o_book, asks) = setup(&stock, &[100, 300, 600]);
+        let exec = o_book.match_order(bid(&stock, 500)).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(
+            fill_qtys(&exec),
+            vec![
+                (asks[0].order_id, 50),
+                (asks[1].order_id, 150),
+                (asks[2].order_id, 300)
+            ]
+        );
+
+        // 2. what's left after rounding down goes to the front of the queue
+        let exec = o_book.match_order(bid(&stock, 99)).unwrap();
+        assert_eq!(
+            fill_qtys(&exec),
+            vec![
+                (asks[0].order_id, 11),
+                (asks[1].order_id, 29),
+                (asks[2].order_id, 59)
+            ]
+        );
+
+        // 3. shares below the minimum allocation are dropped and handed out in queue order
+        stock.min_allocation = 30;
+        let (mut o_book, asks) = setup(&stock, &[300, 100, 600]);
+        let exec = o_book.match_order(bid(&stock, 101)).unwrap();
+        assert_eq!(
+            fill_qtys(&exec),
+            vec![(asks[0].order_id, 41), (asks[2].order_id, 60)]
+        );
+        assert_eq!(o_book.oid_map[&asks[1].order_id].qty, 100);
+
+        // 4. the top order is filled first and the rest is shared out
+        stock.allocation = Allocation::PRO_RATA_TOP_ORDER;
+        stock.min_allocation = 0;
+        let (mut o_book, asks) = setup(&stock, &[100, 300, 600]);
+        let exec = o_book.match_order(bid(&stock, 500)).unwrap();
+        assert_eq!(
+            fill_qtys(&exec),
+            vec![
+                (asks[0].order_id, 100),
+                (asks[1].order_id, 134),
+                (asks[2].order_id, 266)
+            ]
+        );
+
+        // 5. fifo fills in queue order
+        stock.allocation = Allocation::FIFO;
+        let (mut o_book, asks) = setup(&stock, &[100, 300, 600]);
+        let exec = o_book.match_order(bid(&stock, 500)).unwrap();
+        assert_eq!(
+            fill_qtys(&exec),
+            vec![
+                (asks[0].order_id, 100),
+                (asks[1].order_id, 300),
+                (asks[2].order_id, 100)
+            ]
+        );
+
+        // a negative minimum allocation is invalid
+        stock.min_allocation = -1;
+        assert!(stock.validate().is_err());
+    }
+
+    // policy filling the largest resting order first, ties in queue order
+    #[derive(Debug)]
+    struct LargestFirst;
+
+    impl MatchingPolicy for LargestFirst {
+        fn allocate(
+            &self,
+            _stock: &Stock,
+            _incoming: &Order,
+            resting: &[Order],
+            qty: i32,
+        ) -> Vec<(Uuid, i32)> {
+            let mut by_size: Vec<&Order> = resting.iter().collect();
+            by_size.sort_by_key(|order| std::cmp::Reverse(order.visible_qty()));
+
+            let mut remaining = qty;
+            let mut allocations = Vec::new();
+            for order in by_size {
+                let trade_qty = remaining.min(order.visible_qty());
+                if trade_qty == 0 {
+                    break;
+                }
+                allocations.push((order.order_id, trade_qty));
+                remaining -= trade_qty;
+            }
+            allocations
+        }
+    }
+
+    // policy handing out nothing usable, unknown orders and empty or negative allocations
+    #[derive(Debug)]
+    struct Unusable;
+
+    impl MatchingPolicy for Unusable {
+        fn allocate(
+            &self,
+            _stock: &Stock,
+            _incoming: &Order,
+            resting: &[Order],
+            _qty: i32,
+        ) -> Vec<(Uuid, i32)> {
+            let mut allocations = vec![(Uuid::new_v4(), 100)];
+            for order in resting {
+                allocations.push((order.order_id, 0));
+                allocations.push((order.order_id, -5));
+            }
+            allocations
+        }
+    }
+
+    // test plugging a custom matching policy into an orderbook
+    #[test]
+    fn test_custom_matching_policy() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        o_book.set_policy(Arc::new(LargestFirst));
+
+        // 100, 300 and 200 offered at 10.00
+        let mut asks = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        );
+        asks[1].qty = 300;
+        asks[2].qty = 200;
+        for ask in asks.clone() {
+            o_book.add_order(ask).unwrap();
+        }
+
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            450,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        let fills: Vec<(Uuid, i32)> = exec
+            .fills
+            .iter()
+            .map(|fill| (fill.maker_order_id, fill.qty))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![(asks[1].order_id, 300), (asks[2].order_id, 150)]
+        );
+        assert_eq!(o_book.oid_map[&asks[0].order_id].qty, 100);
+        assert_eq!(o_book.oid_map[&asks[2].order_id].qty, 50);
+
+        // built-in allocations can be used as policies too
+        o_book.set_policy(Arc::new(Allocation::FIFO));
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            120,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.fills[0].maker_order_id, asks[0].order_id);
+        assert_eq!(exec.fills[0].qty, 100);
+        assert_eq!(exec.fills[1].maker_order_id, asks[2].order_id);
+        assert_eq!(exec.fills[1].qty, 20);
+
+        // allocations that can't trade are ignored rather than stalling the walk
+        o_book.set_policy(Arc::new(Unusable));
+        let mut bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            50,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        bid.time_in_force = TimeInForce::IOC;
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::CANCEL);
+        assert!(exec.fills.is_empty());
+        assert_eq!(o_book.oid_map.len(), 1);
+        assert_eq!(o_book.oid_map[&asks[2].order_id].qty, 30);
+    }
+
+    // test amending resting orders
+    #[test]
+    fn test_amend_orders() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        let level_orders = |o_book: &mut OrderBook, side: OrderSide, price: f64| {
+            let p_level = o_book.get_price_level(side, stock.price(price)).unwrap();
+            (
+                p_level.orders.iter().cloned().collect::<Vec<Uuid>>(),
+                p_level.qty,
+            )
+        };
+
+        // three asks of 100 at 10.00 and a post-only bid of 100 at 9.90
+        let asks = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        );
+        for ask in asks.clone() {
+            o_book.add_order(ask).unwrap();
+        }
+        let mut bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(9.9),
+            Some(0.0),
+        )[0]
+        .clone();
+        bid.post_only = Some(PostOnly::REJECT);
+        o_book.add_order(bid.clone()).unwrap();
+
+        // 1. reducing the qty keeps the order's place in the queue
+        let exec = o_book
+            .modify_order(asks[0].order_id, 40, asks[0].price)
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MODIFY);
+        assert_eq!(exec.order.qty, 40);
+        assert_eq!(exec.previous_order.as_ref().unwrap().qty, 100);
+        assert_eq!(
+            level_orders(&mut o_book, OrderSide::ASK, 10.0),
+            (
+                vec![asks[0].order_id, asks[1].order_id, asks[2].order_id],
+                240
+            )
+        );
+
+        // 2. increasing it sends the order to the back
+        let exec = o_book
+            .modify_order(asks[1].order_id, 200, asks[1].price)
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MODIFY);
+        assert_eq!(
+            level_orders(&mut o_book, OrderSide::ASK, 10.0),
+            (
+                vec![asks[0].order_id, asks[2].order_id, asks[1].order_id],
+                340
+            )
+        );
+
+        // 3. so does a price change
+        let exec = o_book
+            .modify_order(asks[0].order_id, 40, Some(stock.price(10.05)))
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MODIFY);
+        assert_eq!(
+            exec.previous_order.as_ref().unwrap().price,
+            Some(stock.price(10.0))
+        );
+        assert_eq!(
+            o_book.oid_map[&asks[0].order_id].price,
+            Some(stock.price(10.05))
+        );
+        assert_eq!(
+            level_orders(&mut o_book, OrderSide::ASK, 10.0),
+            (vec![asks[2].order_id, asks[1].order_id], 300)
+        );
+
+        // 4. rejected amends leave the order where it was
+        assert_eq!(
+            o_book.modify_order(bid.order_id, 100, Some(stock.price(10.0))),
+            Err(OrderError::PostOnlyWouldCross)
+        );
+        assert_eq!(
+            o_book.modify_order(bid.order_id, 100, Some(Price::new(9905, 3))),
+            Err(OrderError::InvalidPrice)
+        );
+        assert_eq!(
+            o_book.modify_order(Uuid::new_v4(), 100, Some(stock.price(9.9))),
+            Err(OrderError::InvalidOrderID)
+        );
+        assert_eq!(o_book.oid_map[&bid.order_id], bid);
+        assert_eq!(
+            level_orders(&mut o_book, OrderSide::BID, 9.9),
+            (vec![bid.order_id], 100)
+        );
+
+        // 5. amending into a crossing price trades like a new order
+        let mut bid = bid.clone();
+        bid.post_only = None;
+        o_book.delete_order(bid.order_id).unwrap();
+        o_book.add_order(bid.clone()).unwrap();
+        let exec = o_book
+            .modify_order(bid.order_id, 150, Some(stock.price(10.0)))
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.filled_qty(), 150);
+        assert_eq!(exec.fills[0].maker_order_id, asks[2].order_id);
+        assert_eq!(exec.fills[1].maker_order_id, asks[1].order_id);
+        assert_eq!(
+            exec.previous_order.as_ref().unwrap().price,
+            Some(stock.price(9.9))
+        );
+        assert!(o_book.bid_price_levels.is_empty());
+        assert_eq!(o_book.oid_map[&asks[1].order_id].qty, 150);
+
+        // 6. amending down to nothing deletes the order
+        let exec = o_book
+            .modify_order(asks[1].order_id, 0, asks[1].price)
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::DELETE);
+        assert!(!o_book.oid_map.contains_key(&asks[1].order_id));
+        assert!(o_book
+            .get_price_level(OrderSide::ASK, stock.price(10.0))
+            .is_none());
+    }
+
+    // test cancelling an order and replacing it with a new one in a single step
+    #[test]
+    fn test_cancel_replace() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut exchange = Exchange::new();
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+
+        // two bids of 100 at 9.90 and an ask of 100 at 10.00
+        let bids = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(9.9),
+            Some(0.0),
+        );
+        for bid in bids.clone() {
+            exchange.execute_order(bid).unwrap();
+        }
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        exchange.execute_order(ask.clone()).unwrap();
+        let o_book = |exchange: &Exchange| exchange.orderbooks[&stock.stock_id.to_string()].clone();
+
+        // 1. the replacement rests under its own id, linked to the original
+        let mut replacement = bids[0].clone();
+        replacement.order_id = Uuid::new_v4();
+        replacement.qty = 60;
+        replacement.price = Some(stock.price(9.95));
+        let exec = exchange
+            .cancel_replace(bids[0].order_id, replacement.clone())
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::REPLACE);
+        assert_eq!(exec.order.order_id, replacement.order_id);
+        assert_eq!(exec.order.replaced_order_id, Some(bids[0].order_id));
+        assert_eq!(
+            exec.previous_order.as_ref().unwrap().order_id,
+            bids[0].order_id
+        );
+        let book = o_book(&exchange);
+        assert!(!book.oid_map.contains_key(&bids[0].order_id));
+        assert_eq!(book.best_bid(), Some(stock.price(9.95)));
+        assert!(book.events.is_empty());
+
+        // 2. a replacement that crosses trades straight away
+        let mut crossing = bids[1].clone();
+        crossing.order_id = Uuid::new_v4();
+        crossing.qty = 100;
+        crossing.price = Some(stock.price(10.0));
+        let exec = exchange
+            .cancel_replace(bids[1].order_id, crossing.clone())
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.filled_qty(), 100);
+        assert_eq!(
+            exec.previous_order.as_ref().unwrap().order_id,
+            bids[1].order_id
+        );
+        assert!(o_book(&exchange).ask_price_levels.is_empty());
+
+        // 3. failures leave the book as it was
+        let before = o_book(&exchange);
+        let mut rejected = replacement.clone();
+        rejected.order_id = Uuid::new_v4();
+        rejected.price = Some(stock.price(10.0));
+        rejected.time_in_force = TimeInForce::FOK;
+        assert_eq!(
+            exchange.cancel_replace(replacement.order_id, rejected.clone()),
+            Err(OrderError::InsufficientLiquidity)
+        );
+        rejected.time_in_force = TimeInForce::GTC;
+        rejected.order_side = OrderSide::ASK;
+        assert_eq!(
+            exchange.cancel_replace(replacement.order_id, rejected.clone()),
+            Err(OrderError::InvalidOrderSide)
+        );
+        rejected.order_side = OrderSide::BID;
+        rejected.creator_id = Uuid::new_v4();
+        assert_eq!(
+            exchange.cancel_replace(replacement.order_id, rejected.clone()),
+            Err(OrderError::InvalidCreatorID)
+        );
+        assert_eq!(
+            exchange.cancel_replace(Uuid::new_v4(), replacement.clone()),
+            Err(OrderError::InvalidOrderID)
+        );
+        let after = o_book(&exchange);
+        assert_eq!(after.oid_map, before.oid_map);
+        assert_eq!(
+            after.bid_price_levels[&stock.price(9.95)].orders,
+            before.bid_price_levels[&stock.price(9.95)].orders
+        );
+    }
+
+    // test every execution leaving a book being sequenced and timestamped by the engine
+    #[test]
+    fn test_execution_sequencing() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        let clock = Arc::new(ManualClock::new(1_000));
+        o_book.set_clock(clock.clone());
+
+        // an ask of 100 at 10.00 and a buy stop at 10.00
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let exec = o_book.match_order(ask.clone()).unwrap();
+        assert_eq!(exec.seq_num, 1);
+        assert_eq!(exec.engine_time, 1_000_000_000_000);
+        assert_eq!(exec.time_executed, 1_000);
+
+        let buy_stop = gen_stop_order(
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::STOP,
+            50,
+            None,
+            Some(10.0),
+            None,
+        );
+        let exec = o_book.match_order(buy_stop.clone()).unwrap();
+        assert_eq!(exec.seq_num, 2);
+
+        // rejected orders don't use up a sequence number
+        let mut expired = ask.clone();
+        expired.order_id = Uuid::new_v4();
+        expired.time_in_force = TimeInForce::GTD;
+        expired.expire_time = Some(999);
+        assert_eq!(o_book.match_order(expired), Err(OrderError::OrderExpired));
+
+        // a trade followed by the stop it triggers, numbered in the order they're put out and
+        // timed by the engine whatever creation time the client sent
+        clock.advance(1);
+        let mut bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            20,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        bid.time_created = 5;
+        let exec = o_book.match_order(bid).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MATCH);
+        assert_eq!(exec.seq_num, 3);
+        assert_eq!(exec.engine_time, 1_001_000_000_000);
+        assert_eq!(exec.time_executed, 1_001);
+        assert_eq!(exec.order.time_created, 5);
+        let events = o_book.drain_events();
+        assert!(!events.is_empty());
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event.seq_num, 4 + i as u64);
+            assert_eq!(event.time_executed, 1_001);
+        }
+        assert_eq!(o_book.seq_num, 3 + events.len() as u64);
+
+        // each book keeps its own sequence
+        let mut other_book = orderbook::OrderBook::new(stock.clone());
+        let exec = other_book.match_order(ask).unwrap();
+        assert_eq!(exec.seq_num, 1);
+        assert!(exec.engine_time > 0);
+    }
+
+    // test looking up what happened to orders after they leave the book
+    #[test]
+    fn test_order_status() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut exchange = Exchange::new();
+        exchange.set_clock(clock.clone());
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let creator = Uuid::new_v4();
+        let status = |exchange: &Exchange, order: &Order| {
+            exchange.get_order_status(order.order_id).unwrap().clone()
+        };
+
+        // 100 offered at each of 10.00 and 10.10
+        let mut asks = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.1),
+        );
+        for ask in asks.iter_mut() {
+            ask.creator_id = creator;
+            exchange.execute_order(ask.clone()).unwrap();
+        }
+        assert_eq!(status(&exchange, &asks[0]).status, OrderStatus::NEW);
+
+        // 1. a bid for 150 fills the first ask and half of the second
+        let mut bids = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            150,
+            Some(10.1),
+            Some(-0.1),
+        );
+        exchange.execute_order(bids[0].clone()).unwrap();
+        let bid_state = status(&exchange, &bids[0]);
+        assert_eq!(bid_state.status, OrderStatus::FILLED);
+        assert_eq!(bid_state.filled_qty, 150);
+        assert!((bid_state.avg_price().unwrap() - 10.0333).abs() < 1e-4);
+        assert_eq!(status(&exchange, &asks[0]).status, OrderStatus::FILLED);
+        let ask_state = status(&exchange, &asks[1]);
+        assert_eq!(ask_state.status, OrderStatus::PARTIALLY_FILLED);
+        assert_eq!(ask_state.filled_qty, 50);
+        assert_eq!(ask_state.order.qty, 50);
+        assert_eq!(ask_state.avg_price(), Some(10.1));
+
+        // 2. cancelled, expired and rejected orders
+        bids[1].creator_id = creator;
+        exchange.execute_order(bids[1].clone()).unwrap();
+        exchange.delete_order(bids[1].order_id).unwrap();
+        assert_eq!(status(&exchange, &bids[1]).status, OrderStatus::CANCELLED);
+
+        bids[2].creator_id = creator;
+        bids[2].time_created = 1_000;
+        bids[2].time_in_force = TimeInForce::GTD;
+        bids[2].expire_time = Some(1_010);
+        exchange.execute_order(bids[2].clone()).unwrap();
+        clock.advance(10);
+        exchange.expire_orders();
+        assert_eq!(status(&exchange, &bids[2]).status, OrderStatus::EXPIRED);
+
+        let mut off_tick = bids[0].clone();
+        off_tick.order_id = Uuid::new_v4();
+        off_tick.creator_id = creator;
+        off_tick.price = Some(Price::new(100_005, 4));
+        assert!(exchange.execute_order(off_tick.clone()).is_err());
+        assert_eq!(status(&exchange, &off_tick).status, OrderStatus::REJECTED);
+
+        // 3. a creator's orders, finished or not
+        let creator_orders = exchange.get_creator_orders(creator);
+        assert_eq!(creator_orders.len(), 5);
+        assert!(exchange.get_creator_orders(Uuid::new_v4()).is_empty());
+
+        // 4. finished orders are forgotten once the retention window passes
+        clock.advance(59);
+        exchange.set_status_retention(60);
+        assert!(exchange.get_order_status(bids[0].order_id).is_none());
+        assert!(exchange.get_order_status(asks[0].order_id).is_none());
+        assert!(exchange.get_order_status(bids[2].order_id).is_some());
+        clock.advance(1);
+        exchange.set_status_retention(60);
+        assert!(exchange.get_order_status(bids[2].order_id).is_none());
+        let creator_orders = exchange.get_creator_orders(creator);
+        assert_eq!(creator_orders.len(), 1);
+        assert_eq!(creator_orders[0].order.order_id, asks[1].order_id);
+    }
+
+    // test invalid orders being turned away with a reason code
+    #[test]
+    fn test_order_rejects() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+
+        // invalid orders can't be created
+        let order = Order::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::LIMIT,
+            0,
+            chrono::Utc::now().timestamp() as u32,
+            Some(stock.price(10.0)),
+        );
+        assert_eq!(order, Err(OrderError::InvalidQuantity));
+
+        // or queued
+        let mut order = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        order.qty = -5;
+        let mut o_book = orderbook::OrderBook::new(stock.clone());
+        assert_eq!(
+            o_book.queue_order(order.clone()),
+            Err(OrderError::InvalidQuantity)
+        );
+        assert!(o_book.order_queue.is_empty());
+
+        // limit orders need a price, a priceless one never reaches the book
+        let priceless = Order::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::LIMIT,
+            20,
+            chrono::Utc::now().timestamp() as u32,
+            None,
+        );
+        assert_eq!(priceless, Err(OrderError::InvalidPrice));
+        let mut exchange = Exchange::new();
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            10,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        exchange.execute_order(ask.clone()).unwrap();
+        let mut priceless = order.clone();
+        priceless.qty = 20;
+        priceless.price = None;
+        assert_eq!(
+            exchange.execute_order(priceless),
+            Err(OrderError::InvalidPrice)
+        );
+        let o_book = &exchange.orderbooks[&stock.stock_id.to_string()];
+        assert_eq!(o_book.oid_map[&ask.order_id].qty, 10);
+
+        // reject reports carry a stable code and a readable reason
+        let exec = Execution::reject(order.clone(), &OrderError::PriceNotOnTick, 1_000);
+        assert_eq!(exec.exec_type, ExecutionType::REJECT);
+        assert_eq!(exec.order.order_id, order.order_id);
+        let reject = exec.reject.unwrap();
+        assert_eq!(reject.code, 9);
+        assert_eq!(reject.reason, "price is not a multiple of the tick size");
+        assert_eq!(OrderError::InvalidOrderID.code(), 1);
+        assert_eq!(OrderError::InvalidMarketProtection.code(), 27);
+        assert_eq!(OrderError::Other(String::from("oops")).reason(), "oops");
+    }
+
+    #[test]
+    fn test_order_index_and_mass_cancel() {
+        let stocks: Vec<Stock> = ["AAPL", "MSFT"]
+            .iter()
+            .map(|ticker| {
+                Stock::new(
+                    Uuid::new_v4(),
+                    String::from(*ticker),
+                    String::from(*ticker),
+                    Some(1e6 as i32),
+                    Some(1e6 as i32),
+                    Some(chrono::Utc::now().timestamp() as u32),
+                )
+            })
+            .collect();
+        let mut exchange = Exchange::new();
+        for stock in stocks.iter() {
+            let issuer = User::new(
+                Uuid::new_v4(),
+                String::from("John"),
+                String::from("john.doe@gmail.com"),
+                String::from("password"),
+                Some(1e6_f32),
+            );
+            exchange.add_stock(stock.clone(), issuer).unwrap();
+        }
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+
+        // alice and bob each bid at 9.90-9.80 and offer at 10.10-10.20 on both stocks
+        let mut orders: Vec<Order> = Vec::new();
+        for stock in stocks.iter() {
+            for creator in [alice, bob] {
+                let mut bids = gen_orders(
+                    stock.clone(),
+                    2,
+                    OrderSide::BID,
+                    OrderType::LIMIT,
+                    100,
+                    Some(9.9),
+                    Some(-0.1),
+                );
+                let mut asks = gen_orders(
+                    stock.clone(),
+                    2,
+                    OrderSide::ASK,
+                    OrderType::LIMIT,
+                    100,
+                    Some(10.1),
+                    Some(0.1),
+                );
+                for order in bids.iter_mut().chain(asks.iter_mut()) {
+                    order.creator_id = creator;
+                    exchange.execute_order(order.clone()).unwrap();
+                    orders.push(order.clone());
+                }
+            }
+        }
+        let live = |exchange: &Exchange, order: &Order| {
+            exchange.orderbooks[&order.stock.stock_id.to_string()]
+                .oid_map
+                .contains_key(&order.order_id)
+        };
+
+        // 1. every live order can be found, amended and deleted by id alone
+        for order in orders.iter() {
+            assert_eq!(
+                exchange.get_order_stock(order.order_id),
+                Some(order.stock.stock_id)
+            );
+        }
+        let exec = exchange
+            .modify_order(orders[0].order_id, 50, orders[0].price)
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MODIFY);
+        assert_eq!(exec.order.stock.stock_id, stocks[0].stock_id);
+        let exec = exchange.delete_order(orders[0].order_id).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::DELETE);
+        assert_eq!(exec.order.qty, 50);
+        assert!(!live(&exchange, &orders[0]));
+        assert_eq!(exchange.get_order_stock(orders[0].order_id), None);
+        assert_eq!(
+            exchange.delete_order(orders[0].order_id).err(),
+            Some(OrderError::InvalidOrderID)
+        );
+        assert_eq!(
+            exchange.modify_order(Uuid::new_v4(), 10, None).err(),
+            Some(OrderError::InvalidOrderID)
+        );
+
+        // 2. a filled order leaves the index
+        let taker = gen_orders(
+            stocks[1].clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(10.1),
+            Some(0.0),
+        )
+        .remove(0);
+        exchange.execute_order(taker.clone()).unwrap();
+        let filled = &orders[10];
+        assert_eq!(filled.stock.stock_id, stocks[1].stock_id);
+        assert_eq!(filled.order_side, OrderSide::ASK);
+        assert!(!live(&exchange, filled));
+        assert_eq!(exchange.get_order_stock(filled.order_id), None);
+        assert_eq!(exchange.get_order_stock(taker.order_id), None);
+
+        // 3. cancel alice's bids on the first stock
+        let execs = exchange.mass_cancel(CancelFilter {
+            creator_id: Some(alice),
+            stock_id: Some(stocks[0].stock_id),
+            order_side: Some(OrderSide::BID),
+        });
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].exec_type, ExecutionType::MASS_CANCEL);
+        assert_eq!(execs[0].executor_id, alice);
+        assert_eq!(execs[0].cancelled_orders.len(), 1);
+        assert_eq!(execs[0].cancelled_orders[0].order_id, orders[1].order_id);
+        assert_eq!(
+            exchange
+                .get_order_status(orders[1].order_id)
+                .unwrap()
+                .status,
+            OrderStatus::CANCELLED
+        );
+
+        // 4. cancel everything of bob's
+        let execs = exchange.mass_cancel(CancelFilter {
+            creator_id: Some(bob),
+            ..Default::default()
+        });
+        // one batch per book
+        assert_eq!(execs.len(), 2);
+        let cancelled: Vec<&Order> = execs
+            .iter()
+            .flat_map(|exec| exec.cancelled_orders.iter())
+            .collect();
+        assert_eq!(cancelled.len(), 8);
+        assert!(cancelled.iter().all(|order| order.creator_id == bob));
+        for order in orders.iter().filter(|order| order.creator_id == bob) {
+            assert!(!live(&exchange, order));
+            assert_eq!(exchange.get_order_stock(order.order_id), None);
+        }
+
+        // 5. cancel every ask left on the exchange, leaving alice's bids on the second stock
+        let execs = exchange.mass_cancel(CancelFilter {
+            order_side: Some(OrderSide::ASK),
+            ..Default::default()
+        });
+        assert_eq!(execs.len(), 2);
+        assert_eq!(execs[0].executor_id, Uuid::nil());
+        assert_eq!(
+            execs
+                .iter()
+                .map(|exec| exec.cancelled_orders.len())
+                .sum::<usize>(),
+            3
+        );
+        let remaining: Vec<&Order> = orders
+            .iter()
+            .filter(|order| live(&exchange, order))
+            .collect();
+        assert_eq!(remaining.len(), 2);
+        for order in remaining {
+            assert_eq!(order.creator_id, alice);
+            assert_eq!(order.order_side, OrderSide::BID);
+            assert_eq!(order.stock.stock_id, stocks[1].stock_id);
+        }
+        let execs = exchange.mass_cancel(CancelFilter::default());
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].cancelled_orders.len(), 2);
+        assert!(exchange.order_stocks.is_empty());
+
+        // 6. untriggered stops are amended by id too, and keep waiting for their trigger
+        let stop = gen_stop_order(
+            stocks[0].clone(),
+            OrderSide::BID,
+            OrderType::STOP_LIMIT,
+            100,
+            Some(10.6),
+            Some(10.5),
+            None,
+        );
+        exchange.execute_order(stop.clone()).unwrap();
+        let exec = exchange
+            .modify_order(stop.order_id, 60, Some(stocks[0].price(10.7)))
+            .unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::MODIFY);
+        assert_eq!(exec.previous_order.unwrap().qty, 100);
+        let o_book = &exchange.orderbooks[&stocks[0].stock_id.to_string()];
+        let waiting = &o_book.trigger_book.orders[&stop.order_id];
+        assert_eq!(waiting.qty, 60);
+        assert_eq!(waiting.price, Some(stocks[0].price(10.7)));
+        assert_eq!(
+            exchange.modify_order(stop.order_id, 60, None).err(),
+            Some(OrderError::InvalidPrice)
+        );
+        let exec = exchange.modify_order(stop.order_id, 0, None).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::DELETE);
+        assert!(exchange.orderbooks[&stocks[0].stock_id.to_string()]
+            .trigger_book
+            .is_empty());
+        assert_eq!(exchange.get_order_stock(stop.order_id), None);
+
+        // 7. pegs are only repriced once a batch is out, so none of alice's own are and
+        // bob's moves once
+        let stock = stocks[0].clone();
+        let book_key = stock.stock_id.to_string();
+        let mut orders: Vec<Order> = Vec::new();
+        for (creator, price) in [(bob, 9.9), (alice, 10.0)] {
+            let mut bid = gen_orders(
+                stock.clone(),
+                1,
+                OrderSide::BID,
+                OrderType::LIMIT,
+                100,
+                Some(price),
+                Some(0.0),
+            )
+            .remove(0);
+            bid.creator_id = creator;
+            orders.push(bid);
+        }
+        for creator in [alice, alice, bob] {
+            let mut peg = gen_pegged_order(
+                stock.clone(),
+                OrderSide::BID,
+                100,
+                PegReference::PRIMARY,
+                None,
+            );
+            peg.creator_id = creator;
+            orders.push(peg);
+        }
+        for order in orders.iter() {
+            exchange.execute_order(order.clone()).unwrap();
+        }
+        exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+
+        let execs = exchange.mass_cancel(CancelFilter {
+            creator_id: Some(alice),
+            stock_id: Some(stock.stock_id),
+            order_side: None,
+        });
+        assert_eq!(execs[0].cancelled_orders.len(), 3);
+        let events = exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::MODIFY);
+        assert_eq!(events[0].order.order_id, orders[4].order_id);
+        assert_eq!(events[0].order.price, Some(stock.price(9.9)));
+        assert!(events[0].seq_num > execs[0].seq_num);
+    }
+
+    #[test]
+    fn test_cancel_on_disconnect() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut exchange = Exchange::new();
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let book_key = stock.stock_id.to_string();
+        let creator = Uuid::new_v4();
+        let order = |side: OrderSide, order_type: OrderType, price: Option<f64>| {
+            let mut order =
+                gen_orders(stock.clone(), 1, side, order_type, 100, price, Some(0.0))[0].clone();
+            order.creator_id = creator;
+            order
+        };
+
+        // the creator quotes 9.90/10.10 and has a bracket waiting on an entry at 9.50
+        let bid = order(OrderSide::BID, OrderType::LIMIT, Some(9.9));
+        let ask = order(OrderSide::ASK, OrderType::LIMIT, Some(10.1));
+        exchange.execute_order(bid.clone()).unwrap();
+        exchange.execute_order(ask.clone()).unwrap();
+        let entry = order(OrderSide::BID, OrderType::LIMIT, Some(9.5));
+        let take_profit = order(OrderSide::ASK, OrderType::LIMIT, Some(11.0));
+        let mut stop_loss = gen_stop_order(
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::STOP,
+            100,
+            None,
+            Some(9.0),
+            None,
+        );
+        stop_loss.creator_id = creator;
+        exchange
+            .submit_bracket(entry.clone(), take_profit, stop_loss)
+            .unwrap();
+        exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+
+        // one session covers the creator's bids and pulls them on disconnect, the other doesn't
+        let bid_session = Session::new(Uuid::new_v4(), creator, None, Some(OrderSide::BID), true);
+        let quiet_session = Session::new(Uuid::new_v4(), creator, None, None, false);
+        exchange.open_session(bid_session);
+        exchange.open_session(quiet_session);
+
+        // 1. dropping a session without cancel on disconnect leaves everything in place
+        assert!(exchange.disconnect(quiet_session.session_id).is_empty());
+        assert!(exchange.disconnect(Uuid::new_v4()).is_empty());
+        let o_book = &exchange.orderbooks[&book_key];
+        assert_eq!(o_book.oid_map.len(), 3);
+        let last_seq = o_book.seq_num;
+
+        // 2. both bids are pulled in a single batch, and the bracket's exits go with the entry
+        let execs = exchange.disconnect(bid_session.session_id);
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].exec_type, ExecutionType::MASS_CANCEL);
+        assert_eq!(execs[0].executor_id, creator);
+        assert_eq!(execs[0].seq_num, last_seq + 1);
+        let mut cancelled: Vec<Uuid> = execs[0]
+            .cancelled_orders
+            .iter()
+            .map(|order| order.order_id)
+            .collect();
+        cancelled.sort();
+        let mut expected = vec![bid.order_id, entry.order_id];
+        expected.sort();
+        assert_eq!(cancelled, expected);
+
+        let o_book = exchange.orderbooks.get_mut(&book_key).unwrap();
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|e| e.exec_type == ExecutionType::CANCEL && e.group_id.is_some()));
+        assert_eq!(events[0].seq_num, last_seq + 2);
+        assert_eq!(events[1].seq_num, last_seq + 3);
+        assert_eq!(o_book.oid_map.len(), 1);
+        assert!(o_book.oid_map.contains_key(&ask.order_id));
+        assert_eq!(
+            exchange.get_order_status(bid.order_id).unwrap().status,
+            OrderStatus::CANCELLED
+        );
+        assert_eq!(exchange.get_order_stock(entry.order_id), None);
+        assert!(exchange.sessions.is_empty());
+
+        // 3. the session is gone, a second disconnect does nothing
+        assert!(exchange.disconnect(bid_session.session_id).is_empty());
+
+        // 4. with nothing left to pull there's nothing to report
+        let bid_filter = CancelFilter {
+            creator_id: Some(creator),
+            order_side: Some(OrderSide::BID),
+            ..Default::default()
+        };
+        assert!(exchange.mass_cancel(bid_filter).is_empty());
+        assert!(Execution::mass_cancel(creator, 1_000, Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_exchange_stop_orders() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let mut exchange = Exchange::new();
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let book_key = stock.stock_id.to_string();
+
+        // 100 offered at each of 10.00, 10.50 and 11.00, and a buy stop at 10.50
+        let asks = gen_orders(
+            stock.clone(),
+            3,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.5),
+        );
+        for ask in asks.iter() {
+            exchange.execute_order(ask.clone()).unwrap();
+        }
+        let buy_stop = gen_stop_order(
+            stock.clone(),
+            OrderSide::BID,
+            OrderType::STOP,
+            50,
+            None,
+            Some(10.5),
+            None,
+        );
+        exchange.execute_order(buy_stop.clone()).unwrap();
+        exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+
+        // 1. a bid trading through 10.50 releases the stop, which lifts the rest of 10.50 in
+        // the same call
+        let bid = gen_orders(
+            stock.clone(),
+            2,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            150,
+            Some(10.5),
+            Some(0.0),
+        );
+        let exec = exchange.execute_order(bid[0].clone()).unwrap();
+        assert_eq!(exec.order.order_id, bid[0].order_id);
+        let o_book = exchange.orderbooks.get_mut(&book_key).unwrap();
+        let events = o_book.drain_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].exec_type, ExecutionType::TRIGGER);
+        assert_eq!(events[1].exec_type, ExecutionType::MATCH);
+        assert_eq!(events[1].order.order_id, buy_stop.order_id);
+        assert!(events[0].seq_num < events[1].seq_num);
+        assert!(o_book.order_queue.is_empty());
+        assert!(o_book.trigger_book.is_empty());
+        assert_eq!(
+            exchange.get_order_status(buy_stop.order_id).unwrap().status,
+            OrderStatus::FILLED
+        );
+
+        // 2. the next order gets its own execution back, resting as 10.50 is gone
+        let exec = exchange.execute_order(bid[1].clone()).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::ADD);
+        assert_eq!(exec.order.order_id, bid[1].order_id);
+        assert_eq!(
+            exchange.get_order_status(bid[1].order_id).unwrap().status,
+            OrderStatus::NEW
+        );
+        assert!(exchange.orderbooks[&book_key].order_queue.is_empty());
+    }
+
+    #[test]
+    fn test_order_group_expiry_and_uncross() {
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut exchange = Exchange::new();
+        exchange.set_clock(clock.clone());
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+        let book_key = stock.stock_id.to_string();
+        let creator = Uuid::new_v4();
+        let order = |side: OrderSide, price: f64| {
+            let mut order = gen_orders(
+                stock.clone(),
+                1,
+                side,
+                OrderType::LIMIT,
+                100,
+                Some(price),
+                Some(0.0),
+            )[0]
+            .clone();
+            order.creator_id = creator;
+            order.time_created = 1_000;
+            order
+        };
+
+        // 1. a GTD leg expiring takes its OCO sibling with it
+        let mut gtd_leg = order(OrderSide::BID, 9.0);
+        gtd_leg.time_in_force = TimeInForce::GTD;
+        gtd_leg.expire_time = Some(1_010);
+        let sibling = order(OrderSide::ASK, 11.0);
+        let execs = exchange
+            .submit_oco(gtd_leg.clone(), sibling.clone())
+            .unwrap();
+        let group_id = execs[0].group_id.unwrap();
+        exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+
+        clock.advance(10);
+        let execs = exchange.expire_orders();
+        assert_eq!(execs.len(), 2);
+        assert_eq!(execs[0].exec_type, ExecutionType::EXPIRE);
+        assert_eq!(execs[0].order.order_id, gtd_leg.order_id);
+        assert_eq!(execs[1].exec_type, ExecutionType::CANCEL);
+        assert_eq!(execs[1].order.order_id, sibling.order_id);
+        assert!(exchange.orderbooks[&book_key].oid_map.is_empty());
+        assert!(exchange.get_order_group(group_id).is_none());
+        assert!(exchange.group_orders.is_empty());
+        assert_eq!(
+            exchange.get_order_status(sibling.order_id).unwrap().status,
+            OrderStatus::CANCELLED
+        );
+
+        // 2. an OCO leg filling in an auction uncross cancels its sibling
+        exchange.start_auction(stock.stock_id).unwrap();
+        let bid_leg = order(OrderSide::BID, 10.0);
+        let ask_leg = order(OrderSide::ASK, 12.0);
+        let execs = exchange
+            .submit_oco(bid_leg.clone(), ask_leg.clone())
+            .unwrap();
+        let group_id = execs[0].group_id.unwrap();
+        let mut other_ask = order(OrderSide::ASK, 10.0);
+        other_ask.creator_id = Uuid::new_v4();
+        exchange.execute_order(other_ask).unwrap();
+        exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+
+        let execs = exchange.uncross(stock.stock_id).unwrap();
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].exec_type, ExecutionType::UNCROSS);
+        assert_eq!(execs[0].order.order_id, bid_leg.order_id);
+        let events = exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exec_type, ExecutionType::CANCEL);
+        assert_eq!(events[0].order.order_id, ask_leg.order_id);
+        assert!(exchange.orderbooks[&book_key].oid_map.is_empty());
+        assert!(exchange.get_order_group(group_id).is_none());
+        assert!(exchange.group_orders.is_empty());
+        assert_eq!(
+            exchange.get_order_status(bid_leg.order_id).unwrap().status,
+            OrderStatus::FILLED
+        );
+
+        // 3. a market entry that only partly fills arms its exits for the qty it bought
+        let mut thin_ask = order(OrderSide::ASK, 10.0);
+        thin_ask.creator_id = Uuid::new_v4();
+        thin_ask.qty = 60;
+        exchange.execute_order(thin_ask).unwrap();
+        let mut entry = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::MARKET,
+            100,
+            None,
+            Some(0.0),
+        )[0]
+        .clone();
+        entry.creator_id = creator;
+        let take_profit = order(OrderSide::ASK, 12.0);
+        let mut stop_loss = gen_stop_order(
+            stock.clone(),
+            OrderSide::ASK,
+            OrderType::STOP,
+            100,
+            None,
+            Some(9.0),
+            None,
+        );
+        stop_loss.creator_id = creator;
+        let execs = exchange
+            .submit_bracket(entry.clone(), take_profit.clone(), stop_loss.clone())
+            .unwrap();
+        assert_eq!(execs[0].exec_type, ExecutionType::CANCEL);
+        assert_eq!(execs[0].filled_qty(), 60);
+        let group_id = execs[0].group_id.unwrap();
+        let events = exchange
+            .orderbooks
+            .get_mut(&book_key)
+            .unwrap()
+            .drain_events();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|e| e.exec_type == ExecutionType::ADD && e.order.qty == 60));
+        let group = exchange.get_order_group(group_id).unwrap();
+        assert!(group.armed);
+        assert!(group.entry.is_none());
+        assert_eq!(
+            exchange.orderbooks[&book_key].oid_map[&take_profit.order_id].qty,
+            60
+        );
+        assert_eq!(
+            exchange.orderbooks[&book_key].trigger_book.orders[&stop_loss.order_id].qty,
+            60
+        );
+        assert!(!exchange.group_orders.contains_key(&entry.order_id));
+    }
+
+    // test adding a stock to the exchange
+    #[test]
+    fn test_exchange_add_stock() {
+        // create new exchange
+        let mut exchange = Exchange::new();
+
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+
+        // create new user
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+
+        // add stock to exchange
+        exchange.add_stock(stock, issuer).unwrap();
+
+        // test number of stocks in exchange
+        assert_eq!(exchange.stocks.len(), 1);
+
+        // test to check users and userstocks
+        assert_eq!(exchange.users.len(), 1);
+        assert_eq!(exchange.user_stocks.len(), 1);
+    }
+
+    // test executing order in exchange
+    #[test]
+    fn test_exchange_execute_order() {
+        // create new exchange
+        let mut exchange = Exchange::new();
+
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+
+        // create new user
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+
+        // add stock to exchange
+        exchange.add_stock(stock.clone(), issuer).unwrap();
+
+        // create new order
         let order = Order::new(
             Uuid::new_v4(),
             Uuid::new_v4(),
@@ -1084,8 +4040,9 @@ mod tests {
             orderbook::OrderType::LIMIT,
             100,
             chrono::Utc::now().timestamp() as u32,
-            Some(69.0),
-        );
+            Some(stock.price(69.0)),
+        )
+        .unwrap();
 
         // execute order, and check execution type (should be add)
         let res: Execution = match exchange.execute_order(order) {
@@ -1128,11 +4085,11 @@ mod tests {
             String::from("John"),
             String::from("john.doe@gmail.com"),
             String::from("password"),
-            Some(1e6 as f32),
+            Some(1e6_f32),
         );
 
         // add stock to exchange
-        exchange.add_stock(stock.clone(), issuer);
+        exchange.add_stock(stock.clone(), issuer).unwrap();
 
         // create new orders
         let orders = gen_orders(
@@ -1153,7 +4110,7 @@ mod tests {
 
         // queue orders to order book
         for order in orders {
-            o_book.queue_order(order);
+            o_book.queue_order(order).unwrap();
         }
 
         // insert o_book to exchange
@@ -1184,6 +4141,78 @@ mod tests {
 
         // check order queue length
         assert_eq!(book.order_queue.len(), 0);
+
+        // an unfillable FOK is rejected on its own, the orders around it still execute
+        // and are tracked
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(70.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let mut fok = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            1000000,
+            Some(70.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        fok.time_in_force = TimeInForce::FOK;
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            100,
+            Some(60.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let o_book = exchange
+            .orderbooks
+            .get_mut(&stock.stock_id.to_string())
+            .unwrap();
+        o_book.queue_order(ask.clone()).unwrap();
+        o_book.queue_order(fok.clone()).unwrap();
+        o_book.queue_order(bid.clone()).unwrap();
+
+        let execs = exchange.execute_all_orders().unwrap();
+        let types: Vec<ExecutionType> = execs.iter().map(|e| e.exec_type.clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                ExecutionType::ADD,
+                ExecutionType::REJECT,
+                ExecutionType::ADD
+            ]
+        );
+        assert_eq!(execs[1].order.order_id, fok.order_id);
+        assert_eq!(
+            execs[1].reject.clone().unwrap().code,
+            OrderError::InsufficientLiquidity.code()
+        );
+        assert!(execs.windows(2).all(|w| w[1].seq_num == w[0].seq_num + 1));
+        for order in [&ask, &bid] {
+            assert_eq!(
+                exchange.get_order_status(order.order_id).unwrap().status,
+                OrderStatus::NEW
+            );
+            assert_eq!(
+                exchange.get_order_stock(order.order_id),
+                Some(stock.stock_id)
+            );
+        }
+        assert_eq!(
+            exchange.get_order_status(fok.order_id).unwrap().status,
+            OrderStatus::REJECTED
+        );
     }
 
     // test adding a stock to MatchingEngine
@@ -1208,11 +4237,11 @@ mod tests {
             String::from("John"),
             String::from("john.doe@gmail.com"),
             String::from("password"),
-            Some(1e6 as f32),
+            Some(1e6_f32),
         );
 
         // add stock to matching engine
-        me.add_stock(stock.clone(), issuer);
+        me.add_stock(stock.clone(), issuer).unwrap();
 
         // test number of stocks in matching engine
         let o_book: OrderBook = match me
@@ -1251,7 +4280,7 @@ mod tests {
         );
 
         // establish pub sub connection to matching engine
-        let mut publish_conn = match client.get_async_connection().await {
+        let _publish_conn = match client.get_async_connection().await {
             Ok(c) => c,
             Err(e) => panic!("Error getting connection: {}", e),
         };
@@ -1270,7 +4299,7 @@ mod tests {
             String::from("John"),
             String::from("john.doe@gmail.com"),
             String::from("password"),
-            Some(1e6 as f32),
+            Some(1e6_f32),
         );
 
         // add stock to matching engine
@@ -1288,8 +4317,9 @@ mod tests {
             orderbook::OrderType::LIMIT,
             100,
             chrono::Utc::now().timestamp() as u32,
-            Some(69.0),
-        );
+            Some(stock.price(69.0)),
+        )
+        .unwrap();
 
         // execute order (async), and check execution type (should be add)
         me.execute_order(order.clone()).await.unwrap();
@@ -1299,7 +4329,7 @@ mod tests {
 
         // print msg, and check that you received a msg
         println!("Message: {:?}", msg);
-        assert!(msg.len() > 0);
+        assert!(!msg.is_empty());
         // decode json
         let exec: Execution = match serde_json::from_str(&msg) {
             Ok(r) => r,
@@ -1309,4 +4339,179 @@ mod tests {
         // check order
         assert_eq!(exec.order, order.clone());
     }
+
+    // test rejected orders being published by the MatchingEngine
+    #[tokio::test]
+    async fn test_matching_engine_reject() {
+        // create new matching engine
+        let mut me: MatchingEngine = MatchingEngine::new("redis://127.0.0.1:6379");
+        // new redis client with same address
+        let client = redis::Client::open("redis://127.0.0.1:6379").unwrap();
+
+        // create new stock with a 0.05 tick
+        let mut stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+        stock.tick_size = stock.price(0.05);
+
+        let mut pubsub_conn = client.get_async_connection().await.unwrap().into_pubsub();
+        pubsub_conn
+            .subscribe(format!("stock:{}", stock.clone().ticker))
+            .await
+            .unwrap();
+        pubsub_conn.subscribe(REJECTS_CHANNEL).await.unwrap();
+        let mut pubsub_stream = pubsub_conn.on_message();
+
+        // create new user and add the stock
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        me.add_stock(stock.clone(), issuer).unwrap();
+
+        // an order priced off the tick is rejected, and the rejection published
+        let mut order = Order::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            stock.clone(),
+            orderbook::OrderSide::ASK,
+            orderbook::OrderType::LIMIT,
+            100,
+            chrono::Utc::now().timestamp() as u32,
+            Some(stock.price(69.0)),
+        )
+        .unwrap();
+        order.price = Some(stock.price(69.01));
+        assert_eq!(
+            me.execute_order(order.clone()).await,
+            Err(OrderError::PriceNotOnTick)
+        );
+
+        let msg: String = pubsub_stream.next().await.unwrap().get_payload().unwrap();
+        let exec: Execution = serde_json::from_str(&msg).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::REJECT);
+        assert_eq!(exec.order.order_id, order.order_id);
+        assert_eq!(exec.reject.unwrap().code, OrderError::PriceNotOnTick.code());
+        assert_eq!(exec.seq_num, 1);
+
+        // an order for a stock the engine doesn't list is rejected with its stable code
+        let mut unlisted = order.clone();
+        unlisted.order_id = Uuid::new_v4();
+        unlisted.stock.stock_id = Uuid::new_v4();
+        assert_eq!(
+            me.execute_order(unlisted.clone()).await,
+            Err(OrderError::InvalidStockID)
+        );
+        let msg: String = pubsub_stream.next().await.unwrap().get_payload().unwrap();
+        let exec: Execution = serde_json::from_str(&msg).unwrap();
+        assert_eq!(exec.order.order_id, unlisted.order_id);
+        assert_eq!(exec.reject.unwrap().code, OrderError::InvalidStockID.code());
+
+        // cancelling an unknown order id still produces a REJECT, on the rejects channel
+        let unknown_id = Uuid::new_v4();
+        assert_eq!(
+            me.delete_order(unknown_id).await,
+            Err(OrderError::InvalidOrderID)
+        );
+        let msg = pubsub_stream.next().await.unwrap();
+        assert_eq!(msg.get_channel_name(), REJECTS_CHANNEL);
+        let exec: Execution = serde_json::from_str(&msg.get_payload::<String>().unwrap()).unwrap();
+        assert_eq!(exec.exec_type, ExecutionType::REJECT);
+        assert_eq!(exec.order.order_id, unknown_id);
+        assert_eq!(exec.reject.unwrap().code, OrderError::InvalidOrderID.code());
+    }
+
+    #[tokio::test]
+    async fn test_matching_engine_execute_all_orders() {
+        // create new matching engine
+        let mut me: MatchingEngine = MatchingEngine::new("redis://127.0.0.1:6379");
+        // new redis client with same address
+        let client = redis::Client::open("redis://127.0.0.1:6379").unwrap();
+
+        // create new stock
+        let stock = Stock::new(
+            Uuid::new_v4(),
+            String::from("Apple"),
+            String::from("AAPL"),
+            Some(1e6 as i32),
+            Some(1e6 as i32),
+            Some(chrono::Utc::now().timestamp() as u32),
+        );
+
+        let mut pubsub_conn = client.get_async_connection().await.unwrap().into_pubsub();
+        pubsub_conn
+            .subscribe(format!("stock:{}", stock.clone().ticker))
+            .await
+            .unwrap();
+        let mut pubsub_stream = pubsub_conn.on_message();
+
+        // create new user and add the stock
+        let issuer = User::new(
+            Uuid::new_v4(),
+            String::from("John"),
+            String::from("john.doe@gmail.com"),
+            String::from("password"),
+            Some(1e6_f32),
+        );
+        me.add_stock(stock.clone(), issuer).unwrap();
+
+        // nothing queued, nothing to publish
+        assert_eq!(me.execute_all_orders().await, Ok(Vec::new()));
+
+        // an ask and a bid that crosses it, queued straight onto the book
+        let ask = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::ASK,
+            OrderType::LIMIT,
+            100,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let bid = gen_orders(
+            stock.clone(),
+            1,
+            OrderSide::BID,
+            OrderType::LIMIT,
+            40,
+            Some(10.0),
+            Some(0.0),
+        )[0]
+        .clone();
+        let o_book = me
+            .exchange
+            .orderbooks
+            .get_mut(&stock.stock_id.to_string())
+            .unwrap();
+        o_book.queue_order(ask.clone()).unwrap();
+        o_book.queue_order(bid.clone()).unwrap();
+
+        // executed through the exchange, so both orders reach the registry
+        let execs = me.execute_all_orders().await.unwrap();
+        assert_eq!(execs.len(), 2);
+        assert_eq!(execs[1].exec_type, ExecutionType::MATCH);
+        let ask_state = me.exchange.get_order_status(ask.order_id).unwrap();
+        assert_eq!(ask_state.status, OrderStatus::PARTIALLY_FILLED);
+        assert_eq!(ask_state.filled_qty, 40);
+        assert_eq!(
+            me.exchange.get_order_status(bid.order_id).unwrap().status,
+            OrderStatus::FILLED
+        );
+
+        for expected in execs {
+            let msg: String = pubsub_stream.next().await.unwrap().get_payload().unwrap();
+            let exec: Execution = serde_json::from_str(&msg).unwrap();
+            assert_eq!(exec.seq_num, expected.seq_num);
+            assert_eq!(exec.order.order_id, expected.order.order_id);
+        }
+    }
 }